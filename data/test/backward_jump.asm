@@ -0,0 +1,4 @@
+        MVI A,00000000
+LOOP:   INR A
+        JMP LOOP
+        HLT
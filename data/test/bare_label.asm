@@ -0,0 +1,8 @@
+NOP
+LOOP:
+        MVI A, 5
+NEXT: ; skip
+        JMP LOOP
+        JMP NEXT
+        JMP END
+END:
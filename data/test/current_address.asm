@@ -0,0 +1,7 @@
+MVI A, 1
+HERE: JMP $
+LXI H, $ ; points to itself
+DW $
+ORG 0x10
+MVI B, $
+JNZ HERE
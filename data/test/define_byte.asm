@@ -0,0 +1,4 @@
+        JMP NEXT
+TABLE:  DB 1, 2, 0xFF
+TEXT:   DB 'HI', 0
+NEXT:   HLT
@@ -0,0 +1,4 @@
+START:  MVI A,0
+WORDS:  DW 0x1234, NEXT
+NEXT:   DW START, -1
+        HLT
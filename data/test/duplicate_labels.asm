@@ -1,4 +1,2 @@
-TEST:
-        INR A
-TEST:
-        DCR A
+TEST:   INR A
+TEST:   DCR A
@@ -0,0 +1,6 @@
+        MVI A,0xFF
+        MVI A,255
+        MVI A,-1
+        MVI A,0FFH
+        ADI 1FH
+        ADI 31
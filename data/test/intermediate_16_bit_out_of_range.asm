@@ -0,0 +1 @@
+        LXI H,65536
@@ -0,0 +1 @@
+        MVI A,256
@@ -0,0 +1,4 @@
+        IN 1
+        INR A
+        OUT 0x10
+        INX B
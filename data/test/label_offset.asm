@@ -0,0 +1,6 @@
+BUF: DB 1, 2, 3, 4
+LXI H, BUF+2
+LXI D, BUF + 0x10
+JMP $-3
+DW END-1
+END: HLT
@@ -0,0 +1,2 @@
+LABEL: NOP
+JMP LABEL-1000
@@ -0,0 +1,7 @@
+LDA COUNT
+STA PTR
+LXI H, PTR
+SHLD PTR
+HLT
+COUNT: DB 0
+PTR: DW 0
@@ -0,0 +1,3 @@
+NOP
+LOOP: MVI A, 5 ; init
+        JMP LOOP
@@ -0,0 +1,3 @@
+; store 42 at 0x0100
+        MVI A,0x2A
+        STA 0x0100
@@ -0,0 +1,7 @@
+start:  mvi a,0ffh
+        mov b,a
+        lxi sp,0x100
+        push psw
+        sta 0x20
+        ldax d
+        jmp start
@@ -0,0 +1,11 @@
+; M addresses the memory cell HL points to
+        MVI H, 0x10
+        MVI L, 0x00
+        MVI M, 0x42
+        INR M
+        MOV B, M
+        MVI C, 0x07
+        INX H
+        MOV M, C
+        ADD M
+        HLT
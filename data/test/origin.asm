@@ -0,0 +1,4 @@
+        ORG 0x0100
+START:  MVI A,1
+        JMP START
+        HLT
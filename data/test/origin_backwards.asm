@@ -0,0 +1,4 @@
+        ORG 0x10
+        NOP
+        ORG 0x01
+        NOP
@@ -0,0 +1 @@
+        PUSH SP
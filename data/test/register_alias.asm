@@ -0,0 +1,11 @@
+; COUNTER and PTR stand for B and M
+COUNTER EQU B
+PTR     EQU m ; registers are case-insensitive
+
+        MVI COUNTER,3
+        LXI H,0x0100
+LOOP:   MOV PTR,COUNTER
+        INX H
+        DCR COUNTER
+        JNZ LOOP
+        HLT
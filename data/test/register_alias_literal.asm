@@ -0,0 +1,8 @@
+; register_alias.asm without the aliases
+        MVI B,3
+        LXI H,0x0100
+LOOP:   MOV M,B
+        INX H
+        DCR B
+        JNZ LOOP
+        HLT
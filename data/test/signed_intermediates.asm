@@ -0,0 +1,6 @@
+        ADI -5
+        SUI 200
+        MVI C,-1
+        LXI B,-32768
+        LXI D,65535
+        STA 0x1234
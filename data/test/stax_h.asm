@@ -0,0 +1,2 @@
+        STAX B
+        STAX H
@@ -0,0 +1,13 @@
+; TWICE is called unless the condition fails
+        LXI SP,0x0100
+        MVI A,1
+        CALL TWICE ; 2
+        CPI 2
+        CZ TWICE   ; 4
+        CNZ TWICE  ; 8
+        CC TWICE   ; carry is clear, stays 8
+        CP TWICE   ; 16
+        HLT
+TWICE: ADD A
+        RNC
+        RET
@@ -0,0 +1,6 @@
+ORG 0x0100
+START: MVI A, 1
+LXI H, DATA
+HLT
+DATA: DB 1, 2
+DW START
@@ -0,0 +1,3 @@
+        MVI A,00000000
+        JMP NOPE
+        HLT
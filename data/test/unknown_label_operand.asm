@@ -0,0 +1,2 @@
+LHLD NOPE
+HLT
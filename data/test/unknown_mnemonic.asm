@@ -0,0 +1,4 @@
+        MVI A,1
+        INR A
+        FOO B
+        HLT
@@ -0,0 +1,7 @@
+start:  MVI A,0FFH
+        MOV B,A
+        LXI SP,0x100
+        PUSH PSW
+        STA 0x20
+        LDAX D
+        JMP start
@@ -0,0 +1,465 @@
+use crate::assembler::parser::{
+    self, Instruction, InstructionArgument, InstructionCommand, InstructionRegister,
+    InstructionRegisterPair,
+};
+use std::error::Error;
+use std::fmt;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    // byte that isn't the opcode of any known instruction
+    InvalidOpcode(u8),
+    // the opcode or some of its operand bytes are missing
+    Truncated,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::InvalidOpcode(opcode) => write!(f, "invalid opcode: {:#04x}", opcode),
+            DecodeError::Truncated => write!(f, "truncated instruction"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl Instruction {
+    // decodes the instruction at the start of bytes, returns it with the number of bytes it used
+    pub fn decode_one(bytes: &[u8]) -> Result<(Instruction, usize), DecodeError> {
+        let opcode = *bytes.first().ok_or(DecodeError::Truncated)?;
+        let info = decode_opcode(opcode).ok_or(DecodeError::InvalidOpcode(opcode))?;
+
+        let size = 1 + info.operand_bytes;
+        if size > bytes.len() {
+            return Err(DecodeError::Truncated);
+        }
+
+        Ok((decode_instruction(opcode, info, &bytes[1..size]), size))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpcodeInfo {
+    pub command: InstructionCommand,
+    // number of bytes following the opcode (intermediates and addresses)
+    pub operand_bytes: usize,
+}
+
+pub fn decode_opcode(byte: u8) -> Option<OpcodeInfo> {
+    let (command, operand_bytes) = match byte {
+        // instructions without registers
+        0x00 => (InstructionCommand::Nop, 0),
+        0x76 => (InstructionCommand::Hlt, 0),
+        0x37 => (InstructionCommand::Stc, 0),
+        0x3f => (InstructionCommand::Cmc, 0),
+        0x2f => (InstructionCommand::Cma, 0),
+        0x07 => (InstructionCommand::Rlc, 0),
+        0x0f => (InstructionCommand::Rrc, 0),
+        0x17 => (InstructionCommand::Ral, 0),
+        0x1f => (InstructionCommand::Rar, 0),
+        0x27 => (InstructionCommand::Daa, 0),
+        0xeb => (InstructionCommand::Xchg, 0),
+        0xf9 => (InstructionCommand::Sphl, 0),
+        0xe3 => (InstructionCommand::Xthl, 0),
+        0xe9 => (InstructionCommand::Pchl, 0),
+        0xc9 => (InstructionCommand::Ret, 0),
+        0xd8 => (InstructionCommand::Rc, 0),
+        0xd0 => (InstructionCommand::Rnc, 0),
+        0xc8 => (InstructionCommand::Rz, 0),
+        0xc0 => (InstructionCommand::Rnz, 0),
+        0xf8 => (InstructionCommand::Rm, 0),
+        0xf0 => (InstructionCommand::Rp, 0),
+        0xe8 => (InstructionCommand::Rpe, 0),
+        0xe0 => (InstructionCommand::Rpo, 0),
+        0xfb => (InstructionCommand::Ei, 0),
+        0xf3 => (InstructionCommand::Di, 0),
+        #[cfg(feature = "i8085")]
+        0x20 => (InstructionCommand::Rim, 0),
+        #[cfg(feature = "i8085")]
+        0x30 => (InstructionCommand::Sim, 0),
+        _ if is_undocumented_nop(byte) => (InstructionCommand::Nop, 0),
+
+        // intermediates
+        0xc6 => (InstructionCommand::Adi, 1),
+        0xce => (InstructionCommand::Aci, 1),
+        0xd6 => (InstructionCommand::Sui, 1),
+        0xde => (InstructionCommand::Sbi, 1),
+        0xe6 => (InstructionCommand::Ani, 1),
+        0xee => (InstructionCommand::Xri, 1),
+        0xf6 => (InstructionCommand::Ori, 1),
+        0xfe => (InstructionCommand::Cpi, 1),
+        0xdb => (InstructionCommand::In, 1),
+        0xd3 => (InstructionCommand::Out, 1),
+
+        // 16 bit intermediates and addresses
+        0x32 => (InstructionCommand::Sta, 2),
+        0x3a => (InstructionCommand::Lda, 2),
+        0x22 => (InstructionCommand::Shld, 2),
+        0x2a => (InstructionCommand::Lhld, 2),
+        0xc3 => (InstructionCommand::Jmp, 2),
+        0xda => (InstructionCommand::Jc, 2),
+        0xd2 => (InstructionCommand::Jnc, 2),
+        0xca => (InstructionCommand::Jz, 2),
+        0xc2 => (InstructionCommand::Jnz, 2),
+        0xfa => (InstructionCommand::Jm, 2),
+        0xf2 => (InstructionCommand::Jp, 2),
+        0xea => (InstructionCommand::Jpe, 2),
+        0xe2 => (InstructionCommand::Jpo, 2),
+        0xcd => (InstructionCommand::Call, 2),
+        0xdc => (InstructionCommand::Cc, 2),
+        0xd4 => (InstructionCommand::Cnc, 2),
+        0xcc => (InstructionCommand::Cz, 2),
+        0xc4 => (InstructionCommand::Cnz, 2),
+        0xfc => (InstructionCommand::Cm, 2),
+        0xf4 => (InstructionCommand::Cp, 2),
+        0xec => (InstructionCommand::Cpe, 2),
+        0xe4 => (InstructionCommand::Cpo, 2),
+
+        // instructions with a register pair in the middle
+        0x02 | 0x12 => (InstructionCommand::Stax, 0),
+        0x0a | 0x1a => (InstructionCommand::Ldax, 0),
+        _ if byte & 0b1100_1111 == 0b0000_0001 => (InstructionCommand::Lxi, 2),
+        _ if byte & 0b1100_1111 == 0b0000_1011 => (InstructionCommand::Dcx, 0),
+        _ if byte & 0b1100_1111 == 0b0000_0011 => (InstructionCommand::Inx, 0),
+        _ if byte & 0b1100_1111 == 0b0000_1001 => (InstructionCommand::Dad, 0),
+        _ if byte & 0b1100_1111 == 0b1100_0101 => (InstructionCommand::Push, 0),
+        _ if byte & 0b1100_1111 == 0b1100_0001 => (InstructionCommand::Pop, 0),
+
+        // instructions with a register in the middle
+        _ if byte & 0b1100_0111 == 0b0000_0110 => (InstructionCommand::Mvi, 1),
+        _ if byte & 0b1100_0111 == 0b0000_0100 => (InstructionCommand::Inr, 0),
+        _ if byte & 0b1100_0111 == 0b0000_0101 => (InstructionCommand::Dcr, 0),
+        _ if byte & 0b1100_0111 == 0b1100_0111 => (InstructionCommand::Rst, 0),
+
+        // instructions with registers in the end
+        0x40..=0x7f => (InstructionCommand::Mov, 0),
+        0x80..=0x87 => (InstructionCommand::Add, 0),
+        0x88..=0x8f => (InstructionCommand::Adc, 0),
+        0x90..=0x97 => (InstructionCommand::Sub, 0),
+        0x98..=0x9f => (InstructionCommand::Sbb, 0),
+        0xa0..=0xa7 => (InstructionCommand::Ana, 0),
+        0xa8..=0xaf => (InstructionCommand::Xra, 0),
+        0xb0..=0xb7 => (InstructionCommand::Ora, 0),
+        0xb8..=0xbf => (InstructionCommand::Cmp, 0),
+        _ => return None,
+    };
+
+    Some(OpcodeInfo {
+        command,
+        operand_bytes,
+    })
+}
+
+// 0x08, 0x10, 0x18, 0x20, 0x28, 0x30 and 0x38 aren't documented, on real hardware they do the
+// same as NOP so they're decoded as one, the 8085 uses 0x20 and 0x30 for RIM and SIM.
+// without i8085, 0x20 and 0x30 used to be decode errors, they're NOPs now like the rest
+fn is_undocumented_nop(byte: u8) -> bool {
+    if cfg!(feature = "i8085") && (byte == 0x20 || byte == 0x30) {
+        return false;
+    }
+
+    byte != 0x00 && byte & 0b1100_0111 == 0
+}
+
+// mnemonic without operands and total length in bytes, e.g. 0x31 is ("LXI SP", 3)
+pub fn opcode_info(byte: u8) -> Option<(&'static str, u8)> {
+    static TABLE: OnceLock<Vec<Option<(String, u8)>>> = OnceLock::new();
+
+    let table = TABLE.get_or_init(|| {
+        (0..=255u8)
+            .map(|opcode| {
+                let info = decode_opcode(opcode)?;
+                let size = 1 + info.operand_bytes as u8;
+                let instruction = decode_instruction(opcode, info, &[0, 0]);
+
+                Some((mnemonic(&instruction), size))
+            })
+            .collect()
+    });
+
+    table[byte as usize]
+        .as_ref()
+        .map(|(name, size)| (name.as_str(), *size))
+}
+
+// only the parts encoded in the opcode, operand bytes are left out
+fn mnemonic(instruction: &Instruction) -> String {
+    match instruction {
+        Instruction::SingleRegister(command, register) => format!("{} {}", command, register),
+        Instruction::DoubleRegister(command, (register0, register1)) => {
+            format!("{} {}, {}", command, register0, register1)
+        }
+        Instruction::Intermediate16Bit(command, register_pair, _)
+        | Instruction::PairRegister(command, register_pair) => {
+            format!("{} {}", command, register_pair)
+        }
+        Instruction::IntermediateRegister(command, _, register) => {
+            format!("{} {}", command, register)
+        }
+        Instruction::Restart(command, number) => format!("{} {}", command, number),
+        Instruction::NoRegister(command)
+        | Instruction::Intermediate(command, _)
+        | Instruction::Intermediate16BitNoReg(command, _)
+        | Instruction::Label(command, _)
+        | Instruction::Data(command, _)
+        | Instruction::Origin(command, _) => command.to_string(),
+    }
+}
+
+// builds the instruction for an opcode, operands has to contain the bytes following it
+pub fn decode_instruction(opcode: u8, info: OpcodeInfo, operands: &[u8]) -> Instruction {
+    let bits = parser::int_to_binary(opcode as i16, 8);
+
+    match info.command {
+        InstructionCommand::Mvi => Instruction::IntermediateRegister(
+            InstructionCommand::Mvi,
+            operands[0] as i8,
+            InstructionRegister::decode(&bits[2..5]),
+        ),
+        InstructionCommand::Lxi => Instruction::Intermediate16Bit(
+            InstructionCommand::Lxi,
+            InstructionRegisterPair::decode_for(&info.command, &bits[2..4]),
+            to_word(operands) as i16,
+        ),
+        InstructionCommand::Adi
+        | InstructionCommand::Aci
+        | InstructionCommand::Sui
+        | InstructionCommand::Sbi
+        | InstructionCommand::Ani
+        | InstructionCommand::Xri
+        | InstructionCommand::Ori
+        | InstructionCommand::Cpi
+        | InstructionCommand::In
+        | InstructionCommand::Out => Instruction::Intermediate(info.command, operands[0] as i8),
+        InstructionCommand::Sta
+        | InstructionCommand::Lda
+        | InstructionCommand::Shld
+        | InstructionCommand::Lhld => {
+            Instruction::Intermediate16BitNoReg(info.command, to_word(operands) as i16)
+        }
+        InstructionCommand::Jmp
+        | InstructionCommand::Jc
+        | InstructionCommand::Jnc
+        | InstructionCommand::Jz
+        | InstructionCommand::Jnz
+        | InstructionCommand::Jm
+        | InstructionCommand::Jp
+        | InstructionCommand::Jpe
+        | InstructionCommand::Jpo
+        | InstructionCommand::Call
+        | InstructionCommand::Cc
+        | InstructionCommand::Cnc
+        | InstructionCommand::Cz
+        | InstructionCommand::Cnz
+        | InstructionCommand::Cm
+        | InstructionCommand::Cp
+        | InstructionCommand::Cpe
+        | InstructionCommand::Cpo => Instruction::Label(info.command, to_word(operands)),
+        InstructionCommand::Rst => Instruction::Restart(InstructionCommand::Rst, (opcode >> 3) & 7),
+        InstructionCommand::Stax
+        | InstructionCommand::Ldax
+        | InstructionCommand::Dcx
+        | InstructionCommand::Inx
+        | InstructionCommand::Dad
+        | InstructionCommand::Push
+        | InstructionCommand::Pop => {
+            let register_pair = InstructionRegisterPair::decode_for(&info.command, &bits[2..4]);
+            Instruction::PairRegister(info.command, register_pair)
+        }
+        InstructionCommand::Inr | InstructionCommand::Dcr => {
+            Instruction::SingleRegister(info.command, InstructionRegister::decode(&bits[2..5]))
+        }
+        InstructionCommand::Mov => Instruction::DoubleRegister(
+            InstructionCommand::Mov,
+            (
+                InstructionRegister::decode(&bits[2..5]),
+                InstructionRegister::decode(&bits[5..]),
+            ),
+        ),
+        InstructionCommand::Add
+        | InstructionCommand::Adc
+        | InstructionCommand::Sub
+        | InstructionCommand::Sbb
+        | InstructionCommand::Ana
+        | InstructionCommand::Xra
+        | InstructionCommand::Ora
+        | InstructionCommand::Cmp => {
+            Instruction::SingleRegister(info.command, InstructionRegister::decode(&bits[5..]))
+        }
+        _ => Instruction::NoRegister(info.command),
+    }
+}
+
+// 16 bit operands are stored little-endian, the low byte comes first
+fn to_word(operands: &[u8]) -> u16 {
+    ((operands[1] as u16) << 8) | operands[0] as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::decoder::{
+        decode_instruction, decode_opcode, is_undocumented_nop, opcode_info, DecodeError,
+    };
+    use crate::assembler::parser::{
+        binary_to_int, Instruction, InstructionCommand, InstructionRegister,
+        InstructionRegisterPair,
+    };
+
+    #[test]
+    fn test_decode_opcode() {
+        assert_eq!(
+            decode_opcode(0x00).unwrap().command,
+            InstructionCommand::Nop
+        );
+        assert_eq!(
+            decode_opcode(0x76).unwrap().command,
+            InstructionCommand::Hlt
+        );
+        assert_eq!(
+            decode_opcode(0x36).unwrap().command,
+            InstructionCommand::Mvi
+        );
+        assert_eq!(decode_opcode(0x36).unwrap().operand_bytes, 1);
+        assert_eq!(
+            decode_opcode(0x31).unwrap().command,
+            InstructionCommand::Lxi
+        );
+        assert_eq!(decode_opcode(0x31).unwrap().operand_bytes, 2);
+        assert_eq!(
+            decode_opcode(0x77).unwrap().command,
+            InstructionCommand::Mov
+        );
+        assert_eq!(
+            decode_opcode(0xcd).unwrap().command,
+            InstructionCommand::Call
+        );
+        assert_eq!(decode_opcode(0xcd).unwrap().operand_bytes, 2);
+        assert_eq!(decode_opcode(0xf8).unwrap().command, InstructionCommand::Rm);
+        // 0xdd is an undocumented copy of CALL
+        assert!(decode_opcode(0xdd).is_none());
+    }
+
+    // every opcode the decoder knows has to encode back to the same byte,
+    // except the undocumented NOPs which encode as 0x00
+    #[test]
+    fn test_decode_opcode_round_trip() {
+        for opcode in 0..=255u8 {
+            let info = match decode_opcode(opcode) {
+                Some(info) if !is_undocumented_nop(opcode) => info,
+                _ => continue,
+            };
+
+            let operands = vec![0x12, 0x34];
+            let instruction = decode_instruction(opcode, info, &operands);
+            let encoding = instruction.encode();
+
+            assert_eq!(binary_to_int(&encoding[0..8]) as u8, opcode);
+        }
+    }
+
+    #[test]
+    fn test_opcode_info() {
+        assert_eq!(opcode_info(0x76), Some(("HLT", 1)));
+        assert_eq!(opcode_info(0x31), Some(("LXI SP", 3)));
+        assert_eq!(opcode_info(0x36), Some(("MVI M", 2)));
+        assert_eq!(opcode_info(0x78), Some(("MOV A, B", 1)));
+        assert_eq!(opcode_info(0xf5), Some(("PUSH PSW", 1)));
+        assert_eq!(opcode_info(0xc3), Some(("JMP", 3)));
+        assert_eq!(opcode_info(0xcd), Some(("CALL", 3)));
+        assert_eq!(opcode_info(0xe4), Some(("CPO", 3)));
+        assert_eq!(opcode_info(0xc0), Some(("RNZ", 1)));
+        assert_eq!(opcode_info(0xef), Some(("RST 5", 1)));
+        assert_eq!(opcode_info(0xdd), None);
+    }
+
+    #[test]
+    fn test_decode_one() {
+        assert_eq!(
+            Instruction::decode_one(&[0x87, 0x00]),
+            Ok((
+                Instruction::SingleRegister(InstructionCommand::Add, InstructionRegister::A),
+                1
+            ))
+        );
+        assert_eq!(
+            Instruction::decode_one(&[0x21, 0x34, 0x12, 0x76]),
+            Ok((
+                Instruction::Intermediate16Bit(
+                    InstructionCommand::Lxi,
+                    InstructionRegisterPair::HL,
+                    0x1234
+                ),
+                3
+            ))
+        );
+
+        assert_eq!(Instruction::decode_one(&[]), Err(DecodeError::Truncated));
+        assert_eq!(
+            Instruction::decode_one(&[0x21, 0x12]),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            Instruction::decode_one(&[0xcd, 0x05]),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            Instruction::decode_one(&[0xdd]),
+            Err(DecodeError::InvalidOpcode(0xdd))
+        );
+    }
+
+    #[test]
+    fn test_decode_sp_and_psw() {
+        let decode = |opcode| decode_instruction(opcode, decode_opcode(opcode).unwrap(), &[0, 0]);
+
+        assert_eq!(
+            decode(0xf5),
+            Instruction::PairRegister(InstructionCommand::Push, InstructionRegisterPair::FA)
+        );
+        assert_eq!(
+            decode(0xf1),
+            Instruction::PairRegister(InstructionCommand::Pop, InstructionRegisterPair::FA)
+        );
+        assert_eq!(
+            decode(0x39),
+            Instruction::PairRegister(InstructionCommand::Dad, InstructionRegisterPair::SP)
+        );
+        assert_eq!(
+            decode(0x33),
+            Instruction::PairRegister(InstructionCommand::Inx, InstructionRegisterPair::SP)
+        );
+        assert_eq!(
+            decode(0x31),
+            Instruction::Intermediate16Bit(InstructionCommand::Lxi, InstructionRegisterPair::SP, 0)
+        );
+    }
+
+    #[test]
+    fn test_decode_undocumented_nops() {
+        assert_eq!(
+            Instruction::decode_one(&[0x08]),
+            Ok((Instruction::NoRegister(InstructionCommand::Nop), 1))
+        );
+        assert_eq!(
+            Instruction::decode_one(&[0x38, 0x76]),
+            Ok((Instruction::NoRegister(InstructionCommand::Nop), 1))
+        );
+        assert_eq!(opcode_info(0x18), Some(("NOP", 1)));
+    }
+
+    #[cfg(not(feature = "i8085"))]
+    #[test]
+    fn test_decode_8085_opcodes() {
+        // without the 8085 these are undocumented NOPs
+        assert_eq!(
+            decode_opcode(0x20).unwrap().command,
+            InstructionCommand::Nop
+        );
+        assert_eq!(
+            decode_opcode(0x30).unwrap().command,
+            InstructionCommand::Nop
+        );
+    }
+}
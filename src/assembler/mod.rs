@@ -1,17 +1,104 @@
+pub use crate::assembler::decoder::{opcode_info, DecodeError};
 pub use crate::assembler::parser::{
-    Instruction, InstructionArgument, InstructionCommand, InstructionRegister,
-    InstructionRegisterPair,
+    Diagnostic, Instruction, InstructionArgument, InstructionCommand, InstructionRegister,
+    InstructionRegisterPair, ParseError, Severity,
 };
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{Read, Write};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
 
+mod decoder;
 mod parser;
 
+#[derive(Debug)]
+pub enum AssemblerError {
+    Io(io::Error),
+    Parse(ParseError),
+    // address ORG tried to move back to
+    OriginMovesBackwards(u16),
+    // address and opcode
+    InvalidInstruction(u16, u8),
+    // address of the instruction that ends before all of its bytes are present
+    TruncatedInstruction(u16),
+    // number of instructions found where exactly one was expected
+    NotSingleInstruction(usize),
+    // address of a byte in a binary file whose bits aren't all 0 or 1
+    InvalidBit(u16),
+    // size in bytes of a program that doesn't fit into memory
+    ProgramTooLarge(usize),
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssemblerError::Io(error) => write!(f, "{}", error),
+            AssemblerError::Parse(error) => write!(f, "{}", error),
+            AssemblerError::OriginMovesBackwards(origin) => {
+                write!(f, "ORG can not move backwards: {:#06x}", origin)
+            }
+            AssemblerError::InvalidInstruction(address, opcode) => {
+                write!(
+                    f,
+                    "invalid instruction at {:#06x}: {:#04x}",
+                    address, opcode
+                )
+            }
+            AssemblerError::TruncatedInstruction(address) => {
+                write!(f, "truncated instruction at address {:#06x}", address)
+            }
+            AssemblerError::NotSingleInstruction(count) => {
+                write!(f, "expected a single instruction, found {}", count)
+            }
+            AssemblerError::InvalidBit(address) => {
+                write!(f, "invalid bit in byte at address {:#06x}", address)
+            }
+            AssemblerError::ProgramTooLarge(size) => {
+                write!(f, "program of {} bytes doesn't fit into memory", size)
+            }
+        }
+    }
+}
+
+impl Error for AssemblerError {}
+
+impl From<io::Error> for AssemblerError {
+    fn from(error: io::Error) -> Self {
+        AssemblerError::Io(error)
+    }
+}
+
+impl From<ParseError> for AssemblerError {
+    fn from(error: ParseError) -> Self {
+        AssemblerError::Parse(error)
+    }
+}
+
+// one line of a listing, bytes are the encoding of the instruction as found in the binary
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedLine {
+    pub address: u16,
+    pub instruction: Instruction,
+    pub bytes: Vec<u8>,
+}
+
+// a label and the address it points to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub name: String,
+    pub address: u16,
+}
+
+// reads the source from input_asm and writes the binary to output_bin,
+// the free functions below do the same on strings and byte slices without touching any files
 #[derive(Debug)]
 pub struct Assembler {
     input_asm: String,
     output_bin: String,
+    // resolved by the last assemble, ordered by address
+    labels: Vec<Label>,
 }
 
 impl Assembler {
@@ -19,457 +106,475 @@ impl Assembler {
         Assembler {
             input_asm,
             output_bin,
+            labels: Vec::new(),
         }
     }
 
-    pub fn assemble(&self) {
-        let instructions = parser::parse(self.input_asm.to_owned());
+    pub fn assemble(&mut self) -> Result<(), AssemblerError> {
+        self.assemble_indexed().map(|_| ())
+    }
 
-        // write to file
-        let mut file = File::create(&self.output_bin).unwrap();
-        for instruction in instructions {
-            let encoding = &instruction.encode();
-            file.write_all(encoding).unwrap();
-        }
+    // same as assemble, additionally returns the instructions indexed by their address,
+    // ready to be run without decoding the binary again
+    pub fn assemble_indexed(&mut self) -> Result<Vec<Option<Instruction>>, AssemblerError> {
+        let source = fs::read_to_string(&self.input_asm)?;
+        let (instructions, symbols) = parser::parse_str_with_symbols(&source)?;
+        let placed = place(
+            instructions
+                .into_iter()
+                .map(|(_, instruction)| instruction)
+                .collect(),
+        )?;
+
+        let mut file = File::create(&self.output_bin)?;
+        file.write_all(&to_bits(&placed_bytes(&placed)))?;
+
+        self.labels = sorted_symbols(symbols)
+            .into_iter()
+            .map(|(name, address)| Label { name, address })
+            .collect();
+        let instructions = index_placed(placed);
+
+        Ok(instructions)
     }
 
-    pub fn disassemble(&self, input_bin: String) -> HashMap<u16, Instruction> {
-        let mut file = File::open(input_bin.to_owned()).unwrap();
-        let mut binary_data = Vec::new();
+    // empty until assemble ran
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
 
-        file.read_to_end(&mut binary_data).unwrap();
-        std::fs::remove_file(input_bin).unwrap();
+    // every source line prefixed with its address and bytes, e.g. "0000  3E 2A     MVI A,0x2A",
+    // lines without an instruction are only indented
+    pub fn assemble_with_listing(&self) -> Result<(Vec<u8>, String), AssemblerError> {
+        let source = fs::read_to_string(&self.input_asm)?;
+
+        // bytes and listing come from the same placed instructions
+        let (lines, instructions): (Vec<usize>, Vec<Instruction>) =
+            parser::parse_str_with_lines(&source)?.into_iter().unzip();
+        let placed = place(instructions)?;
+        let bytes = placed_bytes(&placed);
 
-        if binary_data.len() % 8 != 0 {
-            panic!("Data is not proper length!");
+        let mut emitted = HashMap::new();
+        for (line, (address, instruction)) in lines.into_iter().zip(placed) {
+            emitted.insert(line, (address, encode_bytes(&instruction)));
         }
 
-        let mut raw_instructions = Vec::new();
-        for chunk in binary_data.chunks(8) {
-            raw_instructions.push(chunk.to_vec());
+        let mut listing = String::new();
+        for (index, text) in source.lines().enumerate() {
+            let prefix = match emitted.get(&(index + 1)) {
+                Some((address, bytes)) => {
+                    let bytes: Vec<String> =
+                        bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    format!("{:04X}  {:<10}", address, bytes.join(" "))
+                }
+                None => " ".repeat(16),
+            };
+            listing.push_str(format!("{}{}", prefix, text).trim_end());
+            listing.push('\n');
         }
 
-        self.parse_binary_instructions(&raw_instructions)
-    }
-
-    fn parse_binary_instructions(&self, raw_instructions: &[Vec<u8>]) -> HashMap<u16, Instruction> {
-        let mut instructions = HashMap::new();
-
-        let mut index = 0;
-        while index < raw_instructions.len() {
-            // pretty ugly, maybe there is a better solution with match or something
-
-            let instruction: Instruction;
-
-            // instructions that take up more than one byte (intermediates)
-            // MVI
-            if raw_instructions[index][0..2] == [0, 0] && raw_instructions[index][5..] == [1, 1, 0]
-            {
-                let register = InstructionRegister::decode(&raw_instructions[index][2..5]);
-                let intermediate = parser::binary_to_int(&raw_instructions[index + 1].to_vec());
-                instruction = Instruction::IntermediateRegister(
-                    InstructionCommand::Mvi,
-                    intermediate,
-                    register,
-                );
-            // LXI
-            } else if raw_instructions[index][0..2] == [0, 0]
-                && raw_instructions[index][4..] == [0, 0, 0, 1]
-            {
-                let register_pair = InstructionRegisterPair::decode(&raw_instructions[index][2..4]);
-
-                let intermediate0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as i16) << 8;
-                let intermediate1 =
-                    parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as i16;
-                instruction = Instruction::Intermediate16Bit(
-                    InstructionCommand::Lxi,
-                    register_pair,
-                    intermediate0 + intermediate1,
-                );
-            // ADI
-            } else if raw_instructions[index] == vec![1, 1, 0, 0, 0, 1, 1, 0] {
-                let intermediate = parser::binary_to_int(&raw_instructions[index + 1].to_vec());
-                instruction = Instruction::Intermediate(InstructionCommand::Adi, intermediate);
-            // ACI
-            } else if raw_instructions[index] == vec![1, 1, 0, 0, 1, 1, 1, 0] {
-                let intermediate = parser::binary_to_int(&raw_instructions[index + 1].to_vec());
-                instruction = Instruction::Intermediate(InstructionCommand::Aci, intermediate);
-            // SUI
-            } else if raw_instructions[index] == vec![1, 1, 0, 1, 0, 1, 1, 0] {
-                let intermediate = parser::binary_to_int(&raw_instructions[index + 1].to_vec());
-                instruction = Instruction::Intermediate(InstructionCommand::Sui, intermediate);
-
-            // ORI
-            } else if raw_instructions[index] == vec![1, 1, 1, 1, 0, 1, 1, 0] {
-                let intermediate = parser::binary_to_int(&raw_instructions[index + 1].to_vec());
-                instruction = Instruction::Intermediate(InstructionCommand::Ori, intermediate);
-
-            // XRI
-            } else if raw_instructions[index] == vec![1, 1, 1, 0, 1, 1, 1, 0] {
-                let intermediate = parser::binary_to_int(&raw_instructions[index + 1].to_vec());
-                instruction = Instruction::Intermediate(InstructionCommand::Xri, intermediate);
-
-            // ANI
-            } else if raw_instructions[index] == vec![1, 1, 1, 0, 0, 1, 1, 0] {
-                let intermediate = parser::binary_to_int(&raw_instructions[index + 1].to_vec());
-                instruction = Instruction::Intermediate(InstructionCommand::Ani, intermediate);
-
-            // CPI
-            } else if raw_instructions[index] == vec![1, 1, 1, 1, 1, 1, 1, 0] {
-                let intermediate = parser::binary_to_int(&raw_instructions[index + 1].to_vec());
-                instruction = Instruction::Intermediate(InstructionCommand::Cpi, intermediate);
-
-            // SBI
-            } else if raw_instructions[index] == vec![1, 1, 0, 1, 1, 1, 1, 0] {
-                let intermediate = parser::binary_to_int(&raw_instructions[index + 1].to_vec());
-                instruction = Instruction::Intermediate(InstructionCommand::Sbi, intermediate);
-
-            // instructions without registers
-            // HLT
-            } else if raw_instructions[index] == vec![0, 1, 1, 1, 0, 1, 1, 0] {
-                instruction = Instruction::NoRegister(InstructionCommand::Hlt);
-
-            // STC
-            } else if raw_instructions[index] == vec![0, 0, 1, 1, 0, 1, 1, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Stc);
-
-            // CMC
-            } else if raw_instructions[index] == vec![0, 0, 1, 1, 1, 1, 1, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Cmc);
-
-            // CMA
-            } else if raw_instructions[index] == vec![0, 0, 1, 0, 1, 1, 1, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Cma);
-
-            // RLC
-            } else if raw_instructions[index] == vec![0, 0, 0, 0, 0, 1, 1, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Rlc);
-
-            // RRC
-            } else if raw_instructions[index] == vec![0, 0, 0, 0, 1, 1, 1, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Rrc);
-
-            // RAL
-            } else if raw_instructions[index] == vec![0, 0, 0, 1, 0, 1, 1, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Ral);
-
-            // RAR
-            } else if raw_instructions[index] == vec![0, 0, 0, 1, 1, 1, 1, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Rar);
-
-            // DAA
-            } else if raw_instructions[index] == vec![0, 0, 1, 0, 0, 1, 1, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Daa);
-
-            // XCHG
-            } else if raw_instructions[index] == vec![1, 1, 1, 0, 1, 0, 1, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Xchg);
-
-            // SPHL
-            } else if raw_instructions[index] == vec![1, 1, 1, 1, 1, 0, 0, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Sphl);
-
-            // XTHL
-            } else if raw_instructions[index] == vec![1, 1, 1, 0, 0, 0, 1, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Xthl);
-
-            // PCHL
-            } else if raw_instructions[index] == vec![1, 1, 1, 0, 1, 0, 0, 1] {
-                instruction = Instruction::NoRegister(InstructionCommand::Pchl);
-
-            // STA
-            } else if raw_instructions[index] == vec![0, 0, 1, 1, 0, 0, 1, 0] {
-                let intermediate0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as i16) << 8;
-                let intermediate1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as i16) & 255;
-                instruction = Instruction::Intermediate16BitNoReg(
-                    InstructionCommand::Sta,
-                    intermediate0 + intermediate1,
-                )
+        Ok((bytes, listing))
+    }
 
-            // LDA
-            } else if raw_instructions[index] == vec![0, 0, 1, 1, 1, 0, 1, 0] {
-                let intermediate0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as i16) << 8;
-                let intermediate1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as i16) & 255;
-                instruction = Instruction::Intermediate16BitNoReg(
-                    InstructionCommand::Lda,
-                    intermediate0 + intermediate1,
-                )
+    pub fn assemble_to_hex(&self) -> Result<String, AssemblerError> {
+        let source = fs::read_to_string(&self.input_asm)?;
+        assemble_str_to_hex(&source)
+    }
 
-            // SHLD
-            } else if raw_instructions[index] == vec![0, 0, 1, 0, 0, 0, 1, 0] {
-                let intermediate0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as i16) << 8;
-                let intermediate1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as i16) & 255;
-                instruction = Instruction::Intermediate16BitNoReg(
-                    InstructionCommand::Shld,
-                    intermediate0 + intermediate1,
-                )
+    // one byte per entry, starting at address 0
+    pub fn assemble_to_bytes(&self) -> Result<Vec<u8>, AssemblerError> {
+        let source = fs::read_to_string(&self.input_asm)?;
+        assemble_str(&source)
+    }
 
-            // LHLD
-            } else if raw_instructions[index] == vec![0, 0, 1, 0, 1, 0, 1, 0] {
-                let intermediate0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as i16) << 8;
-                let intermediate1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as i16) & 255;
-                instruction = Instruction::Intermediate16BitNoReg(
-                    InstructionCommand::Lhld,
-                    intermediate0 + intermediate1,
-                )
+    // size of the assembled instructions in bytes, without the gaps left by ORG,
+    // and the address of every label, ordered by address
+    pub fn symbol_table(&self) -> Result<(u16, Vec<(String, u16)>), AssemblerError> {
+        let source = fs::read_to_string(&self.input_asm)?;
+        let (instructions, symbols) = parser::parse_str_with_symbols(&source)?;
+        let size: usize = instructions
+            .iter()
+            .map(|(_, instruction)| instruction.get_size() as usize)
+            .sum();
+        let size = u16::try_from(size).map_err(|_| AssemblerError::ProgramTooLarge(size))?;
+
+        Ok((size, sorted_symbols(symbols)))
+    }
 
-            // JMP
-            } else if raw_instructions[index] == vec![1, 1, 0, 0, 0, 0, 1, 1] {
-                let address0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as u16) << 8;
-                let address1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as u16) & 255;
-                instruction = Instruction::Label(InstructionCommand::Jmp, address0 + address1)
-
-            // JC
-            } else if raw_instructions[index] == vec![1, 1, 0, 1, 1, 0, 1, 0] {
-                let address0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as u16) << 8;
-                let address1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as u16) & 255;
-                instruction = Instruction::Label(InstructionCommand::Jc, address0 + address1)
-
-            // JNC
-            } else if raw_instructions[index] == vec![1, 1, 0, 1, 0, 0, 1, 0] {
-                let address0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as u16) << 8;
-                let address1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as u16) & 255;
-                instruction = Instruction::Label(InstructionCommand::Jnc, address0 + address1)
-
-            // JZ
-            } else if raw_instructions[index] == vec![1, 1, 0, 0, 1, 0, 1, 0] {
-                let address0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as u16) << 8;
-                let address1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as u16) & 255;
-                instruction = Instruction::Label(InstructionCommand::Jz, address0 + address1)
-
-            // JNZ
-            } else if raw_instructions[index] == vec![1, 1, 0, 0, 0, 0, 1, 0] {
-                let address0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as u16) << 8;
-                let address1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as u16) & 255;
-                instruction = Instruction::Label(InstructionCommand::Jnz, address0 + address1)
-
-            // JM
-            } else if raw_instructions[index] == vec![1, 1, 1, 1, 1, 0, 1, 0] {
-                let address0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as u16) << 8;
-                let address1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as u16) & 255;
-                instruction = Instruction::Label(InstructionCommand::Jm, address0 + address1)
-
-            // JP
-            } else if raw_instructions[index] == vec![1, 1, 1, 1, 0, 0, 1, 0] {
-                let address0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as u16) << 8;
-                let address1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as u16) & 255;
-                instruction = Instruction::Label(InstructionCommand::Jp, address0 + address1)
-
-            // JPE
-            } else if raw_instructions[index] == vec![1, 1, 1, 0, 1, 0, 1, 0] {
-                let address0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as u16) << 8;
-                let address1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as u16) & 255;
-                instruction = Instruction::Label(InstructionCommand::Jpe, address0 + address1)
-
-            // JPO
-            } else if raw_instructions[index] == vec![1, 1, 1, 0, 0, 0, 1, 0] {
-                let address0 =
-                    (parser::binary_to_int(&raw_instructions[index + 1].to_vec()) as u16) << 8;
-                let address1 =
-                    (parser::binary_to_int(&raw_instructions[index + 2].to_vec()) as u16) & 255;
-                instruction = Instruction::Label(InstructionCommand::Jpo, address0 + address1)
-
-            // instructions with 1 argument in the end
-            // ADD
-            } else if raw_instructions[index][0..5] == [1, 0, 0, 0, 0] {
-                let register = InstructionRegister::decode(&raw_instructions[index][5..]);
-                instruction = Instruction::SingleRegister(InstructionCommand::Add, register);
-
-            // ADC
-            } else if raw_instructions[index][0..5] == [1, 0, 0, 0, 1] {
-                let register = InstructionRegister::decode(&raw_instructions[index][5..]);
-                instruction = Instruction::SingleRegister(InstructionCommand::Adc, register);
-
-            // SUB
-            } else if raw_instructions[index][0..5] == [1, 0, 0, 1, 0] {
-                let register = InstructionRegister::decode(&raw_instructions[index][5..]);
-                instruction = Instruction::SingleRegister(InstructionCommand::Sub, register);
-
-            // ANA
-            } else if raw_instructions[index][0..5] == [1, 0, 1, 0, 0] {
-                let register = InstructionRegister::decode(&raw_instructions[index][5..]);
-                instruction = Instruction::SingleRegister(InstructionCommand::Ana, register);
-
-            // ORA
-            } else if raw_instructions[index][0..5] == [1, 0, 1, 1, 0] {
-                let register = InstructionRegister::decode(&raw_instructions[index][5..]);
-                instruction = Instruction::SingleRegister(InstructionCommand::Ora, register);
-
-            // CMP
-            } else if raw_instructions[index][0..5] == [1, 0, 1, 1, 1] {
-                let register = InstructionRegister::decode(&raw_instructions[index][5..]);
-                instruction = Instruction::SingleRegister(InstructionCommand::Cmp, register);
-
-            // XRA
-            } else if raw_instructions[index][0..5] == [1, 0, 1, 0, 1] {
-                let register = InstructionRegister::decode(&raw_instructions[index][5..]);
-                instruction = Instruction::SingleRegister(InstructionCommand::Xra, register);
-
-            // SBB
-            } else if raw_instructions[index][0..5] == [1, 0, 0, 1, 1] {
-                let register = InstructionRegister::decode(&raw_instructions[index][5..]);
-                instruction = Instruction::SingleRegister(InstructionCommand::Sbb, register);
-
-            // instructions with 1 argument in the middle
-            // instructions with a register pair
-            // STAX
-            } else if raw_instructions[index][0..2] == [0, 0]
-                && raw_instructions[index][4..] == [0, 0, 1, 0]
-            {
-                let register_pair = InstructionRegisterPair::decode(&raw_instructions[index][2..4]);
-                if matches!(register_pair, InstructionRegisterPair::HL)
-                    | matches!(register_pair, InstructionRegisterPair::SP)
-                {
-                    panic!("cannot use SP or HL in this instruction");
-                }
+    // address of a single label resolved by the last assemble,
+    // None if the source doesn't define it
+    pub fn label_address(&self, name: &str) -> Option<u16> {
+        self.labels
+            .iter()
+            .find(|label| label.name == name)
+            .map(|label| label.address)
+    }
 
-                instruction = Instruction::PairRegister(InstructionCommand::Stax, register_pair);
+    // decodes the binary written by assemble
+    pub fn disassemble(&self) -> Result<HashMap<u16, Instruction>, AssemblerError> {
+        Ok(self.disassemble_sorted()?.into_iter().collect())
+    }
 
-            // LDAX
-            } else if raw_instructions[index][0..2] == [0, 0]
-                && raw_instructions[index][4..] == [1, 0, 1, 0]
-            {
-                let register_pair = InstructionRegisterPair::decode(&raw_instructions[index][2..4]);
+    // same as disassemble, ordered by address
+    pub fn disassemble_sorted(&self) -> Result<Vec<(u16, Instruction)>, AssemblerError> {
+        disassemble_bits(&fs::read(&self.output_bin)?)
+    }
 
-                if matches!(register_pair, InstructionRegisterPair::HL)
-                    | matches!(register_pair, InstructionRegisterPair::SP)
-                {
-                    panic!("cannot use SP or HL in this instruction");
-                }
+    // one line per instruction, prefixed with its address
+    pub fn disassemble_to_text(&self, input_bin: String) -> Result<String, AssemblerError> {
+        let bits = fs::read(input_bin)?;
 
-                instruction = Instruction::PairRegister(InstructionCommand::Ldax, register_pair);
-
-            // DCX
-            } else if raw_instructions[index][0..2] == [0, 0]
-                && raw_instructions[index][4..] == [1, 0, 1, 1]
-            {
-                let register_pair = InstructionRegisterPair::decode(&raw_instructions[index][2..4]);
-
-                instruction = Instruction::PairRegister(InstructionCommand::Dcx, register_pair);
-
-            // INX
-            } else if raw_instructions[index][0..2] == [0, 0]
-                && raw_instructions[index][4..] == [0, 0, 1, 1]
-            {
-                let register_pair = InstructionRegisterPair::decode(&raw_instructions[index][2..4]);
-
-                instruction = Instruction::PairRegister(InstructionCommand::Inx, register_pair);
-
-            // DAD
-            } else if raw_instructions[index][0..2] == [0, 0]
-                && raw_instructions[index][4..] == [1, 0, 0, 1]
-            {
-                let register_pair = InstructionRegisterPair::decode(&raw_instructions[index][2..4]);
-
-                instruction = Instruction::PairRegister(InstructionCommand::Dad, register_pair);
-
-            // PUSH
-            } else if raw_instructions[index][0..2] == [1, 1]
-                && raw_instructions[index][4..] == [0, 1, 0, 1]
-            {
-                let register_pair: InstructionRegisterPair;
-                if raw_instructions[index][2..4] == [1, 1] {
-                    register_pair = InstructionRegisterPair::FA;
-                } else {
-                    register_pair = InstructionRegisterPair::decode(&raw_instructions[index][2..4]);
-                }
+        let mut text = String::new();
+        for (address, instruction) in disassemble_bits(&bits)? {
+            text.push_str(&format!("{:04X}: {}\n", address, instruction));
+        }
 
-                instruction = Instruction::PairRegister(InstructionCommand::Push, register_pair);
-
-            // POP
-            } else if raw_instructions[index][0..2] == [1, 1]
-                && raw_instructions[index][4..] == [0, 0, 0, 1]
-            {
-                let register_pair: InstructionRegisterPair;
-                if raw_instructions[index][2..4] == [1, 1] {
-                    register_pair = InstructionRegisterPair::FA;
-                } else {
-                    register_pair = InstructionRegisterPair::decode(&raw_instructions[index][2..4]);
-                }
+        Ok(text)
+    }
+
+    // same as disassemble, but the instructions can be looked up directly by their address
+    pub fn disassemble_indexed(&self) -> Result<Vec<Option<Instruction>>, AssemblerError> {
+        Ok(index_by_address(self.disassemble()?))
+    }
+
+    pub fn disassemble_with_regions(
+        &self,
+        bytes: &[u8],
+        data_ranges: &[(u16, u16)],
+    ) -> Result<Vec<(u16, Instruction)>, AssemblerError> {
+        disassemble_with_regions(bytes, data_ranges)
+    }
 
-                instruction = Instruction::PairRegister(InstructionCommand::Pop, register_pair);
-
-            // instructions with 1 register in the middle
-            // INR
-            } else if raw_instructions[index][0..2] == [0, 0]
-                && raw_instructions[index][5..] == [1, 0, 0]
-            {
-                let register = InstructionRegister::decode(&raw_instructions[index][2..5]);
-                instruction = Instruction::SingleRegister(InstructionCommand::Inr, register);
-            // DCR
-            } else if raw_instructions[index][0..2] == [0, 0]
-                && raw_instructions[index][5..] == [1, 0, 1]
-            {
-                let register = InstructionRegister::decode(&raw_instructions[index][2..5]);
-                instruction = Instruction::SingleRegister(InstructionCommand::Dcr, register);
-
-            // instructions with 2 registers
-            // MOV
-            } else if raw_instructions[index][0..2] == [0, 1] {
-                let registers = (
-                    InstructionRegister::decode(&raw_instructions[index][2..5]),
-                    InstructionRegister::decode(&raw_instructions[index][5..]),
-                );
-
-                instruction = Instruction::DoubleRegister(InstructionCommand::Mov, registers);
+    pub fn disassemble_lines(&self, bytes: &[u8]) -> Result<Vec<DecodedLine>, AssemblerError> {
+        disassemble_lines(bytes)
+    }
+
+    pub fn instructions<'a>(
+        &self,
+        bytes: &'a [u8],
+    ) -> impl Iterator<Item = Result<(u16, Instruction), DecodeError>> + 'a {
+        instructions(bytes)
+    }
+}
+
+// same as Assembler::assemble_to_bytes, but for source that isn't stored in a file
+pub fn assemble_str(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    Ok(placed_bytes(&place(parser::parse_str(source)?)?))
+}
+
+// assemble_str and assemble_str_indexed from a single parse
+pub fn assemble_str_with_index(
+    source: &str,
+) -> Result<(Vec<u8>, Vec<Option<Instruction>>), AssemblerError> {
+    let placed = place(parser::parse_str(source)?)?;
+
+    Ok((placed_bytes(&placed), index_placed(placed)))
+}
+
+fn placed_bytes(placed: &[(u16, Instruction)]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (address, instruction) in placed {
+        for (offset, byte) in encode_bytes(instruction).into_iter().enumerate() {
+            // code running past 0xFFFF continues at 0x0000 like the program counter
+            let at = address.wrapping_add(offset as u16) as usize;
+            if at < bytes.len() {
+                bytes[at] = byte;
             } else {
-                panic!("Invalid instruction!");
+                // fill the gap left by ORG with zeros (NOP)
+                bytes.resize(at, 0);
+                bytes.push(byte);
             }
+        }
+    }
 
-            instructions.insert(index as u16, instruction.clone());
-
-            // skip next byte since its the intermediate of the instruction that was just parsed
-            if matches!(instruction, Instruction::Intermediate(_, _))
-                || matches!(instruction, Instruction::IntermediateRegister(_, _, _))
-            {
-                index += 2;
-            } else if matches!(instruction, Instruction::Intermediate16Bit(_, _, _))
-                || matches!(instruction, Instruction::Intermediate16BitNoReg(_, _))
-                || matches!(instruction, Instruction::Label(_, _))
-            {
-                index += 3;
-            } else {
-                index += 1;
+    bytes
+}
+
+// ordered by address and name
+fn sorted_symbols(symbols: parser::Symbols) -> Vec<(String, u16)> {
+    let mut symbols: Vec<(String, u16)> = symbols.into_iter().collect();
+    symbols.sort_by(|(name0, address0), (name1, address1)| {
+        address0.cmp(address1).then_with(|| name0.cmp(name1))
+    });
+
+    symbols
+}
+
+fn encode_bytes(instruction: &Instruction) -> Vec<u8> {
+    instruction
+        .encode()
+        .chunks(8)
+        .map(|bits| parser::binary_to_int(bits) as u8)
+        .collect()
+}
+
+// Intel HEX with up to 16 bytes per record, gaps left by ORG aren't filled,
+// every contiguous section gets records of its own
+pub fn assemble_str_to_hex(source: &str) -> Result<String, AssemblerError> {
+    // start address and bytes of every section
+    let mut sections: Vec<(u16, Vec<u8>)> = Vec::new();
+    for (address, instruction) in place(parser::parse_str(source)?)? {
+        let mut bytes = encode_bytes(&instruction);
+        match sections.last_mut() {
+            Some((start, section)) if *start as usize + section.len() == address as usize => {
+                section.append(&mut bytes)
+            }
+            _ if bytes.is_empty() => (),
+            _ => sections.push((address, bytes)),
+        }
+    }
+
+    let mut hex = String::new();
+    for (start, bytes) in sections {
+        for (index, chunk) in bytes.chunks(16).enumerate() {
+            let address = start.wrapping_add(index as u16 * 16);
+            hex.push_str(&hex_record(address, chunk));
+        }
+    }
+    // end of file record
+    hex.push_str(":00000001FF\n");
+
+    Ok(hex)
+}
+
+// data record, the checksum makes all bytes of the record add up to 0
+fn hex_record(address: u16, data: &[u8]) -> String {
+    let mut record = vec![data.len() as u8, (address >> 8) as u8, address as u8, 0x00];
+    record.extend_from_slice(data);
+    let checksum = record
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg();
+    record.push(checksum);
+
+    let digits: Vec<String> = record.iter().map(|byte| format!("{:02X}", byte)).collect();
+    format!(":{}\n", digits.join(""))
+}
+
+// same as assemble_str, but the instructions are indexed by their address like disassemble_indexed
+pub fn assemble_str_indexed(source: &str) -> Result<Vec<Option<Instruction>>, AssemblerError> {
+    Ok(index_placed(place(parser::parse_str(source)?)?))
+}
+
+fn index_placed(placed: Vec<(u16, Instruction)>) -> Vec<Option<Instruction>> {
+    let instructions = placed
+        .into_iter()
+        .filter(|(_, instruction)| !matches!(instruction, Instruction::Origin(_, _)))
+        .collect();
+
+    index_by_address(instructions)
+}
+
+// pairs every instruction with its address, ORG moves all following instructions
+fn place(instructions: Vec<Instruction>) -> Result<Vec<(u16, Instruction)>, AssemblerError> {
+    let mut address = 0;
+    let mut placed = Vec::new();
+    for instruction in instructions {
+        if let Instruction::Origin(_, origin) = instruction {
+            if (origin as usize) < address {
+                return Err(AssemblerError::OriginMovesBackwards(origin));
             }
+
+            address = origin as usize;
         }
-        instructions
+
+        let size = instruction.get_size() as usize;
+        placed.push((address as u16, instruction));
+        address += size;
     }
+
+    Ok(placed)
+}
+
+// same as assemble_str, additionally reports suspicious code like instructions after HLT
+pub fn assemble_str_with_diagnostics(
+    source: &str,
+) -> Result<(Vec<u8>, Vec<Diagnostic>), AssemblerError> {
+    let bytes = assemble_str(source)?;
+    let diagnostics = parser::check_unreachable(source)?;
+
+    Ok((bytes, diagnostics))
+}
+
+// assembles exactly one instruction, e.g. for interactive tools
+pub fn assemble_line(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    let instructions = parser::parse_str(source)?;
+    if instructions.len() != 1 {
+        return Err(AssemblerError::NotSingleInstruction(instructions.len()));
+    }
+
+    Ok(encode_bytes(&instructions[0]))
+}
+
+// same as Assembler::assemble, the binary file stores every bit as its own byte
+pub fn assemble_str_to_bits(source: &str) -> Result<Vec<u8>, AssemblerError> {
+    Ok(to_bits(&assemble_str(source)?))
+}
+
+fn to_bits(bytes: &[u8]) -> Vec<u8> {
+    let mut bits = Vec::new();
+    for byte in bytes {
+        bits.append(&mut parser::int_to_binary(*byte as i16, 8));
+    }
+
+    bits
+}
+
+// same as Assembler::disassemble_sorted, but for the contents of a binary file,
+// a broken binary is reported instead of panicking
+pub fn disassemble_bits(bits: &[u8]) -> Result<Vec<(u16, Instruction)>, AssemblerError> {
+    let raw_instructions: Vec<Vec<u8>> = bits.chunks(8).map(|chunk| chunk.to_vec()).collect();
+
+    decode_binary(&raw_instructions)
+}
+
+// instructions in the order they appear in the binary, together with their address
+fn decode_binary(raw_instructions: &[Vec<u8>]) -> Result<Vec<(u16, Instruction)>, AssemblerError> {
+    // an incomplete last byte can't be decoded, the instruction it belongs to is truncated
+    let complete = raw_instructions
+        .iter()
+        .take_while(|raw_instruction| raw_instruction.len() == 8)
+        .count();
+    if let Some(address) = raw_instructions
+        .iter()
+        .position(|raw_instruction| raw_instruction.iter().any(|bit| *bit > 1))
+    {
+        return Err(AssemblerError::InvalidBit(address as u16));
+    }
+
+    let bytes: Vec<u8> = raw_instructions[..complete]
+        .iter()
+        .map(|raw_instruction| parser::binary_to_int(raw_instruction) as u8)
+        .collect();
+
+    let instructions = disassemble_with_regions(&bytes, &[])?;
+    if complete < raw_instructions.len() {
+        return Err(AssemblerError::TruncatedInstruction(complete as u16));
+    }
+
+    Ok(instructions)
+}
+
+// bytes inside of a data range (start inclusive, end exclusive) aren't decoded,
+// every range is emitted as a single DB instead
+pub fn disassemble_with_regions(
+    bytes: &[u8],
+    data_ranges: &[(u16, u16)],
+) -> Result<Vec<(u16, Instruction)>, AssemblerError> {
+    let mut instructions = Vec::new();
+
+    let mut index = 0;
+    while index < bytes.len() {
+        let data_range = data_ranges
+            .iter()
+            .find(|(start, end)| (*start as usize..*end as usize).contains(&index));
+        if let Some((_, end)) = data_range {
+            let end = (*end as usize).min(bytes.len());
+            let data = bytes[index..end].iter().map(|byte| *byte as i8).collect();
+
+            instructions.push((
+                index as u16,
+                Instruction::Data(InstructionCommand::Db, data),
+            ));
+            index = end;
+            continue;
+        }
+
+        let (instruction, size) = match Instruction::decode_one(&bytes[index..]) {
+            Ok(decoded) => decoded,
+            Err(DecodeError::InvalidOpcode(opcode)) => {
+                return Err(AssemblerError::InvalidInstruction(index as u16, opcode));
+            }
+            Err(DecodeError::Truncated) => {
+                return Err(AssemblerError::TruncatedInstruction(index as u16));
+            }
+        };
+        instructions.push((index as u16, instruction));
+
+        index += size;
+    }
+
+    Ok(instructions)
+}
+
+// same as disassemble_with_regions without data ranges, keeps the bytes of every instruction
+pub fn disassemble_lines(bytes: &[u8]) -> Result<Vec<DecodedLine>, AssemblerError> {
+    let lines = disassemble_with_regions(bytes, &[])?
+        .into_iter()
+        .map(|(address, instruction)| {
+            let start = address as usize;
+            let end = start + instruction.get_size() as usize;
+
+            DecodedLine {
+                address,
+                instruction,
+                bytes: bytes[start..end].to_vec(),
+            }
+        })
+        .collect();
+
+    Ok(lines)
+}
+
+// decodes one instruction at a time, iteration ends after the first error
+pub fn instructions<'a>(
+    bytes: &'a [u8],
+) -> impl Iterator<Item = Result<(u16, Instruction), DecodeError>> + 'a {
+    let mut index = 0;
+
+    std::iter::from_fn(move || {
+        if index >= bytes.len() {
+            return None;
+        }
+
+        match Instruction::decode_one(&bytes[index..]) {
+            Ok((instruction, size)) => {
+                let address = index as u16;
+                index += size;
+
+                Some(Ok((address, instruction)))
+            }
+            Err(error) => {
+                index = bytes.len();
+
+                Some(Err(error))
+            }
+        }
+    })
+}
+
+// spreads the instructions over the whole 64 KiB address space
+pub fn index_by_address(instructions: HashMap<u16, Instruction>) -> Vec<Option<Instruction>> {
+    let mut indexed = vec![None; 65536];
+    for (address, instruction) in instructions {
+        indexed[address as usize] = Some(instruction);
+    }
+
+    indexed
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Assembler;
+    use super::{
+        assemble_line, assemble_str, assemble_str_indexed, assemble_str_to_bits,
+        assemble_str_to_hex, assemble_str_with_diagnostics, assemble_str_with_index, decode_binary,
+        disassemble_bits, disassemble_lines, disassemble_with_regions, instructions, Assembler,
+        AssemblerError, DecodeError, DecodedLine, Label, Severity,
+    };
     use crate::assembler::parser::{
-        Instruction, InstructionCommand, InstructionRegister, InstructionRegisterPair,
+        binary_to_int, int_to_binary, Instruction, InstructionCommand, InstructionRegister,
+        InstructionRegisterPair,
     };
+    use std::collections::HashMap;
     use std::fs::File;
     use std::io::Read;
 
+    fn decode_map(
+        raw_instructions: &[Vec<u8>],
+    ) -> Result<HashMap<u16, Instruction>, AssemblerError> {
+        Ok(decode_binary(raw_instructions)?.into_iter().collect())
+    }
+
     #[test]
     fn test_new() {
         let assembler = Assembler::new("test.asm".to_owned(), "test_new_binary".to_owned());
@@ -477,13 +582,42 @@ mod tests {
         assert_eq!("test_new_binary", assembler.output_bin);
     }
 
+    // compares the assembled bytes of source with the golden file next to it,
+    // UPDATE_GOLDEN=1 cargo test rewrites the golden files instead
+    fn assert_golden(source: &str) {
+        let golden = source.replace(".asm", ".bin");
+        let assembler = Assembler::new(source.to_owned(), String::new());
+        let bytes = assembler.assemble_to_bytes().unwrap();
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&golden, &bytes).unwrap();
+            return;
+        }
+
+        let expected = std::fs::read(&golden)
+            .unwrap_or_else(|_| panic!("{} is missing, run with UPDATE_GOLDEN=1", golden));
+        assert_eq!(bytes.len(), expected.len(), "size of {} changed", source);
+        for (address, (byte, expected)) in bytes.iter().zip(expected).enumerate() {
+            assert_eq!(
+                *byte, expected,
+                "{} differs at address {:#06x}",
+                source, address
+            );
+        }
+    }
+
+    #[test]
+    fn test_assemble_golden() {
+        assert_golden("data/test/end_to_end.asm");
+    }
+
     #[test]
     fn test_assemble() {
-        let assembler = Assembler::new(
+        let mut assembler = Assembler::new(
             "data/test/end_to_end.asm".to_owned(),
             "test_assemble_binary".to_owned(),
         );
-        assembler.assemble();
+        assembler.assemble().unwrap();
 
         let mut file = File::open("test_assemble_binary").unwrap();
         let mut binary_data = Vec::new();
@@ -592,88 +726,88 @@ mod tests {
         assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // LXI
         assert_eq!(bytes.next().unwrap(), [0, 0, 1, 1, 0, 0, 0, 1]);
-        // LXI intermediate one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 1, 1, 0, 0, 0, 0]);
-        // LXI intermediate two
+        // LXI intermediate low byte
         assert_eq!(bytes.next().unwrap(), [0, 0, 1, 1, 1, 0, 0, 1]);
+        // LXI intermediate high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 1, 1, 0, 0, 0, 0]);
         // STA
         assert_eq!(bytes.next().unwrap(), [0, 0, 1, 1, 0, 0, 1, 0]);
-        // STA intermediate one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // STA intermediate two
+        // STA intermediate low byte
         assert_eq!(bytes.next().unwrap(), [0, 0, 1, 0, 1, 0, 1, 0]);
+        // STA intermediate high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // LDA
         assert_eq!(bytes.next().unwrap(), [0, 0, 1, 1, 1, 0, 1, 0]);
-        // LDA intermediate one
+        // LDA intermediate low byte
         assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // LDA intermediate two
+        // LDA intermediate high byte
         assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // SHLD
         assert_eq!(bytes.next().unwrap(), [0, 0, 1, 0, 0, 0, 1, 0]);
-        // SHLD intermediate one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 1, 1, 0, 0, 0, 0]);
-        // SHLD intermediate two
+        // SHLD intermediate low byte
         assert_eq!(bytes.next().unwrap(), [0, 0, 1, 1, 1, 0, 0, 1]);
+        // SHLD intermediate high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 1, 1, 0, 0, 0, 0]);
         // LHLD
         assert_eq!(bytes.next().unwrap(), [0, 0, 1, 0, 1, 0, 1, 0]);
-        // LHLD intemediate one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 1, 1, 1, 1]);
-        // LHLD intemediate two
+        // LHLD intermediate low byte
         assert_eq!(bytes.next().unwrap(), [1, 0, 1, 0, 0, 0, 0, 0]);
+        // LHLD intermediate high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 1, 1, 1, 1]);
         // JMP
         assert_eq!(bytes.next().unwrap(), [1, 1, 0, 0, 0, 0, 1, 1]);
-        // JMP address one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // JMP address two
+        // JMP address low byte
         assert_eq!(bytes.next().unwrap(), [0, 1, 0, 1, 1, 0, 1, 0]);
+        // JMP address high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // JC
         assert_eq!(bytes.next().unwrap(), [1, 1, 0, 1, 1, 0, 1, 0]);
-        // JC address one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // JC address two
+        // JC address low byte
         assert_eq!(bytes.next().unwrap(), [0, 1, 0, 1, 1, 0, 1, 0]);
+        // JC address high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // JNC
         assert_eq!(bytes.next().unwrap(), [1, 1, 0, 1, 0, 0, 1, 0]);
-        // JNC address one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // JNC address two
+        // JNC address low byte
         assert_eq!(bytes.next().unwrap(), [0, 1, 0, 1, 1, 0, 1, 0]);
+        // JNC address high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // JZ
         assert_eq!(bytes.next().unwrap(), [1, 1, 0, 0, 1, 0, 1, 0]);
-        // JZ address one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // JZ address two
+        // JZ address low byte
         assert_eq!(bytes.next().unwrap(), [0, 1, 0, 1, 1, 0, 1, 0]);
+        // JZ address high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // JNZ
         assert_eq!(bytes.next().unwrap(), [1, 1, 0, 0, 0, 0, 1, 0]);
-        // JNZ address one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // JNZ address two
+        // JNZ address low byte
         assert_eq!(bytes.next().unwrap(), [0, 1, 0, 1, 1, 0, 1, 0]);
+        // JNZ address high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // JM
         assert_eq!(bytes.next().unwrap(), [1, 1, 1, 1, 1, 0, 1, 0]);
-        // JM address one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // JM address two
+        // JM address low byte
         assert_eq!(bytes.next().unwrap(), [0, 1, 0, 1, 1, 0, 1, 0]);
+        // JM address high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // JP
         assert_eq!(bytes.next().unwrap(), [1, 1, 1, 1, 0, 0, 1, 0]);
-        // JP address one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // JP address two
+        // JP address low byte
         assert_eq!(bytes.next().unwrap(), [0, 1, 0, 1, 1, 0, 1, 0]);
+        // JP address high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // JPE
         assert_eq!(bytes.next().unwrap(), [1, 1, 1, 0, 1, 0, 1, 0]);
-        // JPE address one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // JPE address two
+        // JPE address low byte
         assert_eq!(bytes.next().unwrap(), [0, 1, 0, 1, 1, 0, 1, 0]);
+        // JPE address high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // JPO
         assert_eq!(bytes.next().unwrap(), [1, 1, 1, 0, 0, 0, 1, 0]);
-        // JPO address one
-        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
-        // JPO address two
+        // JPO address low byte
         assert_eq!(bytes.next().unwrap(), [0, 1, 0, 1, 1, 0, 1, 0]);
+        // JPO address high byte
+        assert_eq!(bytes.next().unwrap(), [0, 0, 0, 0, 0, 0, 0, 0]);
         // ADD
         assert_eq!(bytes.next().unwrap(), [1, 0, 0, 0, 0, 0, 0, 0]);
         // ADD
@@ -683,14 +817,167 @@ mod tests {
     }
 
     #[test]
-    fn test_disassemble() {
+    fn test_assemble_with_listing() {
+        let assembler = Assembler::new("data/test/listing.asm".to_owned(), "unused".to_owned());
+        let (bytes, listing) = assembler.assemble_with_listing().unwrap();
+
+        assert_eq!(bytes, vec![0x3e, 0x2a, 0x32, 0x00, 0x01]);
+        assert_eq!(
+            listing,
+            concat!(
+                "                ; store 42 at 0x0100\n",
+                "0000  3E 2A             MVI A,0x2A\n",
+                "0002  32 00 01          STA 0x0100\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_assemble_to_bytes() {
+        let mut assembler = Assembler::new(
+            "data/test/end_to_end.asm".to_owned(),
+            "test_assemble_to_bytes_binary".to_owned(),
+        );
+        let bytes = assembler.assemble_to_bytes().unwrap();
+        assembler.assemble().unwrap();
+
+        let mut file = File::open("test_assemble_to_bytes_binary").unwrap();
+        let mut binary_data = Vec::new();
+
+        file.read_to_end(&mut binary_data).unwrap();
+        std::fs::remove_file("test_assemble_to_bytes_binary").unwrap();
+
+        let file_bytes: Vec<u8> = binary_data
+            .chunks(8)
+            .map(|bits| binary_to_int(bits) as u8)
+            .collect();
+        assert_eq!(bytes, file_bytes);
+        assert_eq!(bytes.len(), 92);
+        // MVI A,00011100
+        assert_eq!(bytes[0..2], [0x3E, 0x1C]);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        // the directory doesn't exist, so the output can't be created
+        let mut assembler = Assembler::new(
+            "data/test/end_to_end.asm".to_owned(),
+            "data/test/missing/output".to_owned(),
+        );
+        assert!(matches!(assembler.assemble(), Err(AssemblerError::Io(_))));
+
+        let mut assembler = Assembler::new(
+            "data/test/unknown_mnemonic.asm".to_owned(),
+            "test_assemble_errors_binary".to_owned(),
+        );
+        assert!(matches!(
+            assembler.assemble(),
+            Err(AssemblerError::Parse(_))
+        ));
+        assert!(File::open("test_assemble_errors_binary").is_err());
+    }
+
+    #[test]
+    fn test_assemble_str_to_hex() {
+        let hex = assemble_str_to_hex("MVI A,1\nHLT\nORG 0x4000\nDB 1, 2, 3\n").unwrap();
+
+        assert_eq!(
+            hex,
+            concat!(
+                ":030000003E017648\n",
+                ":03400000010203B7\n",
+                ":00000001FF\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_assemble_to_hex() {
+        let assembler = Assembler::new("data/test/origin.asm".to_owned(), "unused".to_owned());
+        // no records for the zeros in front of ORG 0x0100
+        assert_eq!(
+            assembler.assemble_to_hex().unwrap(),
+            ":060100003E01C300017680\n:00000001FF\n"
+        );
+    }
+
+    #[test]
+    fn test_assemble_str() {
+        let bytes = assemble_str("START:  MVI A,0x2A\n        INR A\n        JMP START\n").unwrap();
+
+        assert_eq!(bytes, vec![0x3E, 0x2A, 0x3C, 0xC3, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_assemble_str_with_index() {
+        let source = std::fs::read_to_string("data/test/origin.asm").unwrap();
+        let (bytes, instructions) = assemble_str_with_index(&source).unwrap();
+
+        assert_eq!(bytes, assemble_str(&source).unwrap());
+        assert_eq!(instructions, assemble_str_indexed(&source).unwrap());
+    }
+
+    #[test]
+    fn test_assemble_str_wraps_around() {
+        let bytes = assemble_str("ORG 0xFFFF\nLXI H,0\nLOOP: NOP\nJMP LOOP\n").unwrap();
+
+        assert_eq!(bytes.len(), 0x10000);
+        assert_eq!(bytes[0xffff], 0x21);
+        assert_eq!(&bytes[0..6], &[0x00, 0x00, 0x00, 0xc3, 0x02, 0x00]);
+    }
+
+    #[test]
+    fn test_assemble_str_parse_error() {
+        let error = assemble_str("        MVI A,0x2A\n        FOO\n").unwrap_err();
+
+        match error {
+            AssemblerError::Parse(error) => {
+                assert_eq!(error.line, 2);
+                assert_eq!(error.message, "unknown mnemonic");
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_assemble_to_bytes_origin_backwards() {
         let assembler = Assembler::new(
+            "data/test/origin_backwards.asm".to_owned(),
+            "unused".to_owned(),
+        );
+        let error = assembler.assemble_to_bytes().unwrap_err();
+
+        assert!(matches!(
+            error,
+            AssemblerError::OriginMovesBackwards(0x0001)
+        ));
+    }
+
+    #[test]
+    fn test_disassemble_sorted() {
+        let mut assembler = Assembler::new(
+            "data/test/end_to_end.asm".to_owned(),
+            "test_disassemble_sorted_binary".to_owned(),
+        );
+        assembler.assemble().unwrap();
+
+        let instructions = assembler.disassemble_sorted().unwrap();
+        std::fs::remove_file("test_disassemble_sorted_binary").unwrap();
+        assert_eq!(instructions.len(), 55);
+        assert_eq!(instructions[0].0, 0);
+        assert!(instructions.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let mut assembler = Assembler::new(
             "data/test/end_to_end.asm".to_owned(),
             "test_disassemble_binary".to_owned(),
         );
-        assembler.assemble();
+        assembler.assemble().unwrap();
 
-        let instructions = assembler.disassemble("test_disassemble_binary".to_owned());
+        let instructions = assembler.disassemble().unwrap();
+        std::fs::remove_file("test_disassemble_binary").unwrap();
         assert_eq!(instructions.len(), 55);
 
         let mut instruction = instructions.get(&0).unwrap();
@@ -1020,36 +1307,563 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
-    fn test_if_corrupted_binary_file() {
+    fn test_disassemble_origin() {
+        let mut assembler = Assembler::new(
+            "data/test/origin.asm".to_owned(),
+            "test_disassemble_origin_binary".to_owned(),
+        );
+        assembler.assemble().unwrap();
+
+        let instructions = assembler.disassemble().unwrap();
+        std::fs::remove_file("test_disassemble_origin_binary").unwrap();
+
+        // the gap up to the origin is filled with NOPs
+        assert_eq!(
+            *instructions.get(&0).unwrap(),
+            Instruction::NoRegister(InstructionCommand::Nop)
+        );
+        assert_eq!(
+            *instructions.get(&0x0100).unwrap(),
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 1, InstructionRegister::A)
+        );
+        assert_eq!(
+            *instructions.get(&0x0102).unwrap(),
+            Instruction::Label(InstructionCommand::Jmp, 0x0100)
+        );
+    }
+
+    #[test]
+    fn test_disassemble_indexed() {
+        let mut assembler = Assembler::new(
+            "data/test/origin.asm".to_owned(),
+            "test_disassemble_indexed_binary".to_owned(),
+        );
+        assembler.assemble().unwrap();
+
+        let instructions = assembler.disassemble_indexed().unwrap();
+        std::fs::remove_file("test_disassemble_indexed_binary").unwrap();
+
+        assert_eq!(instructions.len(), 65536);
+        assert_eq!(
+            instructions[0x0100],
+            Some(Instruction::IntermediateRegister(
+                InstructionCommand::Mvi,
+                1,
+                InstructionRegister::A
+            ))
+        );
+        // the intermediate of MVI is not an instruction
+        assert_eq!(instructions[0x0101], None);
+        assert_eq!(
+            instructions[0x0102],
+            Some(Instruction::Label(InstructionCommand::Jmp, 0x0100))
+        );
+        assert_eq!(
+            instructions[0x0105],
+            Some(Instruction::NoRegister(InstructionCommand::Hlt))
+        );
+        assert_eq!(instructions[0x0106], None);
+    }
+
+    #[test]
+    fn test_disassemble_to_text() {
+        let mut assembler = Assembler::new(
+            "data/test/end_to_end.asm".to_owned(),
+            "test_disassemble_to_text_binary".to_owned(),
+        );
+        assembler.assemble().unwrap();
+
+        let text = assembler
+            .disassemble_to_text("test_disassemble_to_text_binary".to_owned())
+            .unwrap();
+        std::fs::remove_file("test_disassemble_to_text_binary").unwrap();
+
+        let mut lines = text.lines();
+        assert_eq!(lines.next().unwrap(), "0000: MVI A, 0x1C");
+        assert_eq!(lines.next().unwrap(), "0002: MOV A, B");
+        assert_eq!(lines.next().unwrap(), "0003: ANA B");
+        assert_eq!(lines.next().unwrap(), "0004: ADD A");
+        assert_eq!(lines.next().unwrap(), "0005: SUB A");
+        assert_eq!(lines.next().unwrap(), "0006: INR A");
+    }
+
+    #[test]
+    fn test_stateless_round_trip() {
+        let source = "MVI A,0x1C\nLXI H,0x0102\nDB 0,0\nHLT\n";
+        let bits = assemble_str_to_bits(source).unwrap();
+
+        // the same bits Assembler writes into the binary file
+        let output = "test_stateless_round_trip_binary";
+        std::fs::write("test_stateless_round_trip.asm", source).unwrap();
+        Assembler::new(
+            "test_stateless_round_trip.asm".to_owned(),
+            output.to_owned(),
+        )
+        .assemble()
+        .unwrap();
+        assert_eq!(std::fs::read(output).unwrap(), bits);
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file("test_stateless_round_trip.asm").unwrap();
+
+        // the DB bytes decode as two NOPs
+        let decoded = disassemble_bits(&bits).unwrap();
+        assert_eq!(decoded.len(), 5);
+        assert_eq!(decoded[1].0, 2);
+        assert_eq!(decoded[2].0, 5);
+        assert_eq!(decoded[4].0, 7);
+        assert_eq!(
+            decoded[4].1,
+            Instruction::NoRegister(InstructionCommand::Hlt)
+        );
+
+        let bytes = assemble_str(source).unwrap();
+        assert_eq!(disassemble_with_regions(&bytes, &[]).unwrap(), decoded);
+        assert_eq!(
+            disassemble_lines(&bytes).unwrap()[1].bytes,
+            [0x21, 0x02, 0x01]
+        );
+        assert_eq!(instructions(&bytes).count(), 5);
+    }
+
+    #[test]
+    fn test_disassemble_bits_errors() {
+        assert!(matches!(
+            disassemble_bits(&[0, 0, 0, 0, 0, 0, 0, 2]),
+            Err(AssemblerError::InvalidBit(0))
+        ));
+        // MVI without its immediate
+        assert!(matches!(
+            disassemble_bits(&[0, 0, 1, 1, 1, 1, 1, 0]),
+            Err(AssemblerError::TruncatedInstruction(0))
+        ));
+        assert!(disassemble_bits(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_disassemble_to_text_corrupted_binary_file() {
         let assembler = Assembler::new(
             "test.asm".to_owned(),
             "data/test/corrupted_binary_file".to_owned(),
         );
-        assembler.disassemble("data/test/corrupted_binary_file".to_string());
+        let error = assembler
+            .disassemble_to_text("data/test/corrupted_binary_file".to_string())
+            .unwrap_err();
+
+        assert!(matches!(error, AssemblerError::TruncatedInstruction(0)));
     }
 
     #[test]
-    #[should_panic]
-    fn test_if_unknown_instruction() {
+    fn test_decode_binary_sp_and_psw() {
+        let raw_instructions: Vec<Vec<u8>> = assemble_str("PUSH PSW\nDAD SP\n")
+            .unwrap()
+            .iter()
+            .map(|byte| int_to_binary(*byte as i16, 8))
+            .collect();
+
+        assert_eq!(
+            decode_binary(&raw_instructions).unwrap(),
+            vec![
+                (
+                    0,
+                    Instruction::PairRegister(
+                        InstructionCommand::Push,
+                        InstructionRegisterPair::FA
+                    )
+                ),
+                (
+                    1,
+                    Instruction::PairRegister(InstructionCommand::Dad, InstructionRegisterPair::SP)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_binary_invalid_bit() {
+        let raw_instructions = vec![vec![0; 8], vec![0, 1, 1, 1, 0, 2, 1, 0]];
+
+        assert!(matches!(
+            decode_binary(&raw_instructions),
+            Err(AssemblerError::InvalidBit(1))
+        ));
+    }
+
+    // arbitrary input decodes or fails with an error, but never panics
+    #[test]
+    fn test_decode_random_bytes() {
+        let assembler = Assembler::new("test.asm".to_owned(), "test.bin".to_owned());
+
+        // xorshift, the same bytes on every run
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..500 {
+            let len = (next() % 64) as usize;
+            let bytes: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            let start = (next() % 64) as u16;
+            let end = (next() % 64) as u16;
+
+            let _ = assembler.disassemble_lines(&bytes);
+            let _ = assembler.disassemble_with_regions(&bytes, &[(start, end)]);
+            let _ = assembler.instructions(&bytes).count();
+
+            let raw_instructions: Vec<Vec<u8>> = bytes
+                .chunks((next() % 9 + 1) as usize)
+                .map(|chunk| chunk.iter().map(|bit| bit % 3).collect())
+                .collect();
+            let _ = decode_binary(&raw_instructions);
+        }
+
+        // every opcode with every number of operand bytes, including too few
+        for opcode in 0..=255u8 {
+            for len in 0..4 {
+                let bytes = [opcode, 0xff, 0xff, 0xff];
+                let _ = Instruction::decode_one(&bytes[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unreachable_after_halt() {
+        let (bytes, diagnostics) =
+            assemble_str_with_diagnostics("MVI A, 1\nHLT\nINR A\nHLT\n").unwrap();
+        assert_eq!(bytes, assemble_str("MVI A, 1\nHLT\nINR A\nHLT\n").unwrap());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].error.line, 3);
+        assert_eq!(diagnostics[0].error.text, "INR A");
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "warning: line 3, column 1: unreachable instruction after HLT: \"INR A\""
+        );
+
+        // labeled code can be jumped to, data after HLT is fine as well
+        let source = "JMP NEXT\nHLT\nNEXT: INR A\nJMP LAST\nHLT\nDB 1\nLAST:\nHLT\n";
+        let (_, diagnostics) = assemble_str_with_diagnostics(source).unwrap();
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_assemble_line() {
+        assert_eq!(assemble_line("ADD A").unwrap(), vec![0x87]);
+        assert_eq!(
+            assemble_line("MVI A, 0x10 ; load").unwrap(),
+            vec![0x3e, 0x10]
+        );
+
+        let error = assemble_line("ADD A\nADD B").unwrap_err();
+        assert!(matches!(error, AssemblerError::NotSingleInstruction(2)));
+        assert_eq!(error.to_string(), "expected a single instruction, found 2");
+
+        let error = assemble_line("; nothing").unwrap_err();
+        assert!(matches!(error, AssemblerError::NotSingleInstruction(0)));
+    }
+
+    #[test]
+    fn test_mov_m_m() {
+        let error = match assemble_line("MOV M, M").unwrap_err() {
+            AssemblerError::Parse(error) => error,
+            error => panic!("unexpected error: {}", error),
+        };
+        assert_eq!(error.message, "invalid move from M to M");
+        assert_eq!(error.column, 8);
+
+        assert_eq!(assemble_line("MOV M, A").unwrap(), vec![0x77]);
+
+        // 0x76 decodes to HLT, never to MOV M, M
+        assert_eq!(
+            Instruction::decode_one(&[0x76]).unwrap().0,
+            Instruction::NoRegister(InstructionCommand::Hlt)
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "loop: MVI A, 1\nJMP loop\n";
+        let semicolon = "; counts up\nloop: MVI A, 1 ; a = 1\n;\nJMP loop ;again, $+3 & more\n";
+        let hash = "# counts up\nloop: MVI A, 1 # a = 1\n#\nJMP loop #again, $+3 & more\n";
+
+        assert_eq!(
+            assemble_str(semicolon).unwrap(),
+            assemble_str(source).unwrap()
+        );
+        assert_eq!(assemble_str(hash).unwrap(), assemble_str(source).unwrap());
+    }
+
+    #[test]
+    fn test_blank_lines() {
+        let source = "MVI A, 1\nloop: INR A\nCPI 5\nJNZ loop\nHLT\n";
+        let padded =
+            "\n\n\tMVI A, 1  \n\n \t\n  loop:\tINR A \t\n\n\tCPI 5\n JNZ loop ; back\n\n\nHLT\t";
+
+        assert_eq!(assemble_str(padded).unwrap(), assemble_str(source).unwrap());
+    }
+
+    #[test]
+    fn test_symbol_table() {
+        let assembler = Assembler::new(
+            "data/test/symbol_table.asm".to_owned(),
+            "test_symbol_table_binary".to_owned(),
+        );
+
+        let (size, symbols) = assembler.symbol_table().unwrap();
+        assert_eq!(size, 10);
+        assert_eq!(
+            symbols,
+            vec![("START".to_owned(), 0x100), ("DATA".to_owned(), 0x106)]
+        );
+    }
+
+    #[test]
+    fn test_symbol_table_program_too_large() {
+        std::fs::write(
+            "test_symbol_table_program_too_large.asm",
+            format!("DB '{}'\n", "A".repeat(0x8000)).repeat(2),
+        )
+        .unwrap();
+        let assembler = Assembler::new(
+            "test_symbol_table_program_too_large.asm".to_owned(),
+            "test_symbol_table_program_too_large_binary".to_owned(),
+        );
+
+        let error = assembler.symbol_table().unwrap_err();
+        std::fs::remove_file("test_symbol_table_program_too_large.asm").unwrap();
+        assert!(matches!(error, AssemblerError::ProgramTooLarge(0x10000)));
+    }
+
+    #[test]
+    fn test_little_endian_operands() {
+        let assembler = Assembler::new("test.asm".to_owned(), "test.bin".to_owned());
+
+        let bytes = assemble_line("STA 0x4000").unwrap();
+        assert_eq!(bytes, vec![0x32, 0x00, 0x40]);
+        assert_eq!(
+            assembler.disassemble_with_regions(&bytes, &[]).unwrap(),
+            vec![(
+                0,
+                Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 0x4000)
+            )]
+        );
+
+        assert_eq!(
+            assemble_line("LHLD 0x1234").unwrap(),
+            vec![0x2a, 0x34, 0x12]
+        );
+        assert_eq!(
+            assemble_line("LXI SP, 0x1234").unwrap(),
+            vec![0x31, 0x34, 0x12]
+        );
+        assert_eq!(assemble_line("JMP $").unwrap(), vec![0xc3, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_disassemble_io() {
+        let assembler = Assembler::new("test.asm".to_owned(), "test.bin".to_owned());
+
+        assert_eq!(
+            assembler
+                .disassemble_with_regions(&[0xdb, 0x01, 0xd3, 0x02], &[])
+                .unwrap(),
+            vec![
+                (0, Instruction::Intermediate(InstructionCommand::In, 0x01)),
+                (2, Instruction::Intermediate(InstructionCommand::Out, 0x02)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_label_address() {
+        let mut assembler = Assembler::new(
+            "data/test/backward_jump.asm".to_owned(),
+            "test_label_address_binary".to_owned(),
+        );
+        assert!(assembler.labels().is_empty());
+
+        assembler.assemble().unwrap();
+        std::fs::remove_file("test_label_address_binary").unwrap();
+        assert_eq!(
+            assembler.labels(),
+            &[Label {
+                name: "LOOP".to_owned(),
+                address: 2
+            }]
+        );
+        assert_eq!(assembler.label_address("LOOP"), Some(2));
+        assert_eq!(assembler.label_address("loop"), None);
+        assert_eq!(
+            assembler.symbol_table().unwrap().1,
+            vec![("LOOP".to_owned(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_lines() {
+        let assembler = Assembler::new("test.asm".to_owned(), "test.bin".to_owned());
+        let bytes = assemble_str("NOP\nLXI H, 0x1234\nHLT\n").unwrap();
+
+        let lines = assembler.disassemble_lines(&bytes).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            DecodedLine {
+                address: 1,
+                instruction: Instruction::Intermediate16Bit(
+                    InstructionCommand::Lxi,
+                    InstructionRegisterPair::HL,
+                    0x1234
+                ),
+                bytes: vec![0x21, 0x34, 0x12],
+            }
+        );
+        assert_eq!(lines[2].address, 4);
+        assert_eq!(lines[2].bytes, vec![0x76]);
+    }
+
+    #[test]
+    fn test_instructions() {
+        let mut assembler = Assembler::new(
+            "data/test/end_to_end.asm".to_owned(),
+            "test_instructions_binary".to_owned(),
+        );
+        let bytes = assembler.assemble_to_bytes().unwrap();
+        assembler.assemble().unwrap();
+
+        let instructions: Vec<(u16, Instruction)> = assembler
+            .instructions(&bytes)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let expected = assembler.disassemble_sorted().unwrap();
+        std::fs::remove_file("test_instructions_binary").unwrap();
+        assert_eq!(instructions, expected);
+
+        // nothing is decoded after an error
+        let mut instructions = assembler.instructions(&[0x00, 0xdd, 0x00]);
+        assert_eq!(
+            instructions.next(),
+            Some(Ok((0, Instruction::NoRegister(InstructionCommand::Nop))))
+        );
+        assert_eq!(
+            instructions.next(),
+            Some(Err(DecodeError::InvalidOpcode(0xdd)))
+        );
+        assert_eq!(instructions.next(), None);
+    }
+
+    #[test]
+    fn test_disassemble_with_regions() {
+        let assembler = Assembler::new("test.asm".to_owned(), "test.bin".to_owned());
+        let bytes = assemble_str("MVI A, 1\nHLT\nDB 0xFE, 0xFE\nNOP\n").unwrap();
+
+        // without a data range the table is read as CPI 0xFE
+        assert_eq!(
+            assembler.disassemble_with_regions(&bytes, &[]).unwrap()[2],
+            (3, Instruction::Intermediate(InstructionCommand::Cpi, -2))
+        );
+
+        assert_eq!(
+            assembler
+                .disassemble_with_regions(&bytes, &[(3, 5)])
+                .unwrap(),
+            vec![
+                (
+                    0,
+                    Instruction::IntermediateRegister(
+                        InstructionCommand::Mvi,
+                        1,
+                        InstructionRegister::A
+                    )
+                ),
+                (2, Instruction::NoRegister(InstructionCommand::Hlt)),
+                (3, Instruction::Data(InstructionCommand::Db, vec![-2, -2])),
+                (5, Instruction::NoRegister(InstructionCommand::Nop)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_binary_truncated_instruction() {
+        // NOP followed by MVI A without its intermediate
+        let raw_instructions = vec![vec![0, 0, 0, 0, 0, 0, 0, 0], vec![0, 0, 1, 1, 1, 1, 1, 0]];
+        let error = decode_binary(&raw_instructions).unwrap_err();
+        assert!(matches!(error, AssemblerError::TruncatedInstruction(1)));
+        assert_eq!(error.to_string(), "truncated instruction at address 0x0001");
+
+        // LXI with only half of its address
+        let raw_instructions = vec![
+            vec![0, 0, 1, 1, 0, 0, 0, 1],
+            vec![0, 0, 0, 1, 0, 0, 1, 0],
+            vec![0, 0, 1, 1],
+        ];
+        let error = decode_binary(&raw_instructions).unwrap_err();
+        assert!(matches!(error, AssemblerError::TruncatedInstruction(0)));
+    }
+
+    #[test]
+    fn test_if_corrupted_binary_file() {
         let assembler = Assembler::new(
             "test.asm".to_owned(),
-            "test_if_unknown_instruction_binary".to_owned(),
+            "data/test/corrupted_binary_file".to_owned(),
         );
+
+        assert!(assembler.disassemble().is_err());
+        // the binary is only read, never removed
+        assert!(std::path::Path::new("data/test/corrupted_binary_file").exists());
+    }
+
+    #[test]
+    fn test_disassemble_missing_file() {
+        let assembler = Assembler::new("test.asm".to_owned(), "missing_binary".to_owned());
+
+        assert!(matches!(
+            assembler.disassemble().unwrap_err(),
+            AssemblerError::Io(_)
+        ));
+    }
+
+    #[test]
+    fn test_if_unknown_instruction() {
         let instruction = vec![vec![0, 0, 0, 0, 0, 0, 0, 1]];
 
-        assembler.parse_binary_instructions(&instruction);
+        assert!(decode_map(&instruction).is_err());
+    }
+
+    // the index has to move past both operand bytes of every 3 byte instruction
+    #[test]
+    fn test_three_byte_instructions_parsing() {
+        let bytes = assemble_str("STA 1\nLDA 2\nSHLD 3\n").unwrap();
+        let raw_instructions: Vec<Vec<u8>> = bytes
+            .iter()
+            .map(|byte| int_to_binary(*byte as i16, 8))
+            .collect();
+
+        let instructions = decode_map(&raw_instructions).unwrap();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(
+            instructions[&0],
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 1)
+        );
+        assert_eq!(
+            instructions[&3],
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Lda, 2)
+        );
+        assert_eq!(
+            instructions[&6],
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Shld, 3)
+        );
     }
 
     // test ldax and sdax separately since only one register pair is tested
     // in test_disassemble()
     #[test]
     fn test_stax_parsing() {
-        let assembler =
-            Assembler::new("test.asm".to_owned(), "test_stax_parsing_binary".to_owned());
         let instruction = vec![vec![0, 0, 0, 0, 0, 0, 1, 0]];
 
-        let instructions = &assembler.parse_binary_instructions(&instruction);
+        let instructions = &decode_map(&instruction).unwrap();
         let instruction = instructions.get(&0).unwrap();
 
         assert_eq!(
@@ -1059,7 +1873,7 @@ mod tests {
 
         let instruction = vec![vec![0, 0, 0, 1, 0, 0, 1, 0]];
 
-        let instructions = &assembler.parse_binary_instructions(&instruction);
+        let instructions = &decode_map(&instruction).unwrap();
         let instruction = instructions.get(&0).unwrap();
 
         assert_eq!(
@@ -1070,11 +1884,9 @@ mod tests {
 
     #[test]
     fn test_ldax_parsing() {
-        let assembler =
-            Assembler::new("test.asm".to_owned(), "test_ldax_parsing_binary".to_owned());
         let instruction = vec![vec![0, 0, 0, 0, 1, 0, 1, 0]];
 
-        let instructions = &assembler.parse_binary_instructions(&instruction);
+        let instructions = &decode_map(&instruction).unwrap();
         let instruction = instructions.get(&0).unwrap();
 
         assert_eq!(
@@ -1084,7 +1896,7 @@ mod tests {
 
         let instruction = vec![vec![0, 0, 0, 1, 1, 0, 1, 0]];
 
-        let instructions = &assembler.parse_binary_instructions(&instruction);
+        let instructions = &decode_map(&instruction).unwrap();
         let instruction = instructions.get(&0).unwrap();
 
         assert_eq!(
@@ -1,32 +1,185 @@
-use pest::iterators::Pairs;
-use pest::Parser;
+use pest::error::{ErrorVariant, LineColLocation};
+use pest::iterators::{Pair, Pairs};
+use pest::{Parser, Span};
 use std::collections::HashMap;
-use std::fs;
+use std::error::Error;
+use std::fmt;
+use std::mem;
 use std::str::FromStr;
-use strum_macros::EnumString;
+use strum_macros::{Display, EnumString};
 
 #[derive(Parser)]
 #[grammar = "asm.pest"]
 pub struct AssemblyParser;
 
-pub fn parse(file_name: String) -> Vec<Instruction> {
-    let unparsed_file = fs::read_to_string(file_name).unwrap();
-    let assembly = AssemblyParser::parse(Rule::assembly, &unparsed_file)
-        .expect("unsuccessful parse")
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(span: Span, message: &str) -> ParseError {
+        let (line, column) = span.start_pos().line_col();
+
+        ParseError {
+            line,
+            column,
+            text: span.as_str().to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    fn from_syntax_error(error: pest::error::Error<Rule>) -> ParseError {
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(position) => position,
+            LineColLocation::Span(start, _) => start,
+        };
+
+        // the offending text is the token the parser choked on
+        let text = error
+            .line()
+            .chars()
+            .skip(column - 1)
+            .take_while(|char| !char.is_whitespace())
+            .collect();
+
+        let message = match &error.variant {
+            ErrorVariant::ParsingError { positives, .. } => {
+                if positives.iter().any(|rule| {
+                    matches!(
+                        rule,
+                        Rule::register
+                            | Rule::pair_reg_register
+                            | Rule::label_parameter
+                            | Rule::label_expression
+                    )
+                }) {
+                    "invalid operand"
+                } else if positives.iter().any(|rule| {
+                    matches!(
+                        rule,
+                        Rule::intermediate | Rule::intermediate_16_bit | Rule::data_byte
+                    )
+                }) {
+                    "invalid intermediate"
+                } else {
+                    "unknown mnemonic"
+                }
+            }
+            ErrorVariant::CustomError { message } => message,
+        };
+
+        ParseError {
+            line,
+            column,
+            text,
+            message: message.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}: {:?}",
+            self.line, self.column, self.message, self.text
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    // assembling goes on, the code is most likely a mistake
+    Warning,
+}
+
+// problems that don't stop assembling, located the same way as a ParseError
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub error: ParseError,
+}
+
+impl Diagnostic {
+    fn warning(span: Span, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            error: ParseError::new(span, message),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.error),
+        }
+    }
+}
+
+// the assembler reads the source itself, reading files here is only used by the tests
+#[cfg(test)]
+pub fn parse(file_name: String) -> Result<Vec<Instruction>, ParseError> {
+    let unparsed_file = std::fs::read_to_string(file_name).unwrap();
+    parse_str(&unparsed_file)
+}
+
+pub fn parse_str(source: &str) -> Result<Vec<Instruction>, ParseError> {
+    Ok(parse_str_with_lines(source)?
+        .into_iter()
+        .map(|(_, instruction)| instruction)
+        .collect())
+}
+
+// same as parse_str, every instruction comes with the line it was written on
+pub fn parse_str_with_lines(source: &str) -> Result<Vec<(usize, Instruction)>, ParseError> {
+    Ok(parse_str_with_symbols(source)?.0)
+}
+
+// address of every label by its name
+pub type Symbols = HashMap<String, u16>;
+
+// same as parse_str_with_lines, additionally returns the address of every label
+pub fn parse_str_with_symbols(
+    source: &str,
+) -> Result<(Vec<(usize, Instruction)>, Symbols), ParseError> {
+    let assembly = AssemblyParser::parse(Rule::assembly, source)
+        .map_err(ParseError::from_syntax_error)?
         .next()
         .unwrap();
 
     let raw_instructions = assembly.into_inner();
 
-    let labels = parse_labels(raw_instructions.clone());
+    let labels = parse_labels(raw_instructions.clone())?;
+    let aliases = parse_aliases(raw_instructions.clone(), &labels)?;
 
     let mut instructions = Vec::new();
+    let mut lines = Vec::new();
+    // address of the current instruction, operands refer to it with $
+    let mut address = 0;
 
     for instruction in raw_instructions {
         let rule = instruction.as_rule();
 
-        // ignore comments and end of input
-        if !matches!(rule, Rule::comment | Rule::EOI) {
+        if matches!(rule, Rule::computed_call) {
+            return Err(ParseError::new(
+                instruction.as_span(),
+                "CALL can't jump to a register pair, use PCHL",
+            ));
+        }
+
+        // ignore comments, labels on their own line, aliases and end of input
+        if !matches!(
+            rule,
+            Rule::comment | Rule::label_definition | Rule::equ_definition | Rule::EOI
+        ) {
+            lines.push(instruction.as_span().start_pos().line_col().0);
             let mut inner_instruction_pairs = instruction.into_inner();
             let inner_instruction = inner_instruction_pairs.peek().unwrap();
 
@@ -39,145 +192,74 @@ pub fn parse(file_name: String) -> Vec<Instruction> {
             let mut pairs = inner_instruction_pairs.peek().unwrap().into_inner();
             let inner_instruction = pairs.peek().unwrap();
             rule = inner_instruction.as_rule();
-            let command = InstructionCommand::from_str(inner_instruction.as_str()).unwrap();
+            // RIM and SIM are only known with the i8085 feature
+            let command = InstructionCommand::from_str(&inner_instruction.as_str().to_uppercase())
+                .map_err(|_| ParseError::new(inner_instruction.as_span(), "unknown mnemonic"))?;
             pairs.next();
 
             match rule {
                 Rule::intermediate_reg_command => {
-                    let register =
-                        InstructionRegister::from_str(pairs.peek().unwrap().as_str()).unwrap();
+                    let register = parse_register(pairs.peek().unwrap(), &aliases)?;
                     pairs.next();
 
-                    let mut intermediate = Vec::new();
-                    for char in pairs.as_str().chars() {
-                        if char == '0' {
-                            intermediate.push(0);
-                        } else {
-                            intermediate.push(1);
-                        }
-                    }
+                    let intermediate = parse_intermediate(pairs.peek().unwrap(), address)?;
 
-                    let instruction = Instruction::IntermediateRegister(
-                        command,
-                        binary_to_int(&intermediate),
-                        register,
-                    );
+                    let instruction =
+                        Instruction::IntermediateRegister(command, intermediate, register);
 
                     instructions.push(instruction);
                 }
                 Rule::intermediate_16_bit_command => {
-                    let register_pair: InstructionRegisterPair;
-
-                    let unparsed_register = pairs.peek().unwrap().as_str();
-
-                    // TODO make this prettier
-                    if unparsed_register == "SP" {
-                        register_pair = InstructionRegisterPair::SP;
-                    } else if unparsed_register == "PSW" {
-                        register_pair = InstructionRegisterPair::FA;
-                    } else {
-                        match InstructionRegister::from_str(unparsed_register).unwrap() {
-                            InstructionRegister::B => register_pair = InstructionRegisterPair::BC,
-                            InstructionRegister::D => register_pair = InstructionRegisterPair::DE,
-                            InstructionRegister::H => register_pair = InstructionRegisterPair::DE,
-                            _ => panic!("invalid register"),
-                        }
-                    }
-
-                    pairs.next();
+                    let register_pair = parse_register_pair(pairs.peek().unwrap());
+                    validate_register_pair(&command, &register_pair, pairs.next().unwrap())?;
 
-                    let mut raw_intermediate = Vec::new();
-                    for char in pairs.as_str().chars() {
-                        if char == '0' {
-                            raw_intermediate.push(0);
-                        } else {
-                            raw_intermediate.push(1);
-                        }
-                    }
+                    let intermediate = parse_address(pairs.peek().unwrap(), &labels, address)?;
 
-                    let high_bits = (binary_to_int(&raw_intermediate[0..8]) as i16) << 8;
-                    let low_bits = binary_to_int(&raw_intermediate[8..16]) as i16;
-
-                    let instruction = Instruction::Intermediate16Bit(
-                        command,
-                        register_pair,
-                        high_bits + low_bits,
-                    );
+                    let instruction =
+                        Instruction::Intermediate16Bit(command, register_pair, intermediate);
                     instructions.push(instruction);
                 }
                 Rule::double_reg_command => {
-                    let register0 =
-                        InstructionRegister::from_str(pairs.peek().unwrap().as_str()).unwrap();
+                    let register0 = parse_register(pairs.peek().unwrap(), &aliases)?;
                     pairs.next();
 
-                    let register1 =
-                        InstructionRegister::from_str(pairs.peek().unwrap().as_str()).unwrap();
-                    pairs.next();
+                    // the opcode of MOV M, M is HLT
+                    let operand = pairs.next().unwrap();
+                    let register1 = parse_register(operand.clone(), &aliases)?;
+                    if register0 == InstructionRegister::M && register1 == InstructionRegister::M {
+                        return Err(ParseError::new(
+                            operand.as_span(),
+                            "invalid move from M to M",
+                        ));
+                    }
 
                     let instruction = Instruction::DoubleRegister(command, (register0, register1));
                     instructions.push(instruction);
                 }
                 Rule::single_reg_command => {
-                    let register =
-                        InstructionRegister::from_str(pairs.peek().unwrap().as_str()).unwrap();
+                    let register = parse_register(pairs.peek().unwrap(), &aliases)?;
                     pairs.next();
 
                     let instruction = Instruction::SingleRegister(command, register);
                     instructions.push(instruction);
                 }
                 Rule::pair_reg_command => {
-                    let register_pair: InstructionRegisterPair;
-
-                    let unparsed_register = pairs.peek().unwrap().as_str();
-
-                    // TODO make this prettier
-                    if unparsed_register == "SP" {
-                        register_pair = InstructionRegisterPair::SP;
-                    } else if unparsed_register == "PSW" {
-                        register_pair = InstructionRegisterPair::FA;
-                    } else {
-                        match InstructionRegister::from_str(unparsed_register).unwrap() {
-                            InstructionRegister::B => register_pair = InstructionRegisterPair::BC,
-                            InstructionRegister::D => register_pair = InstructionRegisterPair::DE,
-                            InstructionRegister::H => register_pair = InstructionRegisterPair::DE,
-                            _ => panic!("invalid register"),
-                        }
-                    }
-
-                    pairs.next();
+                    let register_pair = parse_register_pair(pairs.peek().unwrap());
+                    validate_register_pair(&command, &register_pair, pairs.next().unwrap())?;
 
                     let instruction = Instruction::PairRegister(command, register_pair);
                     instructions.push(instruction);
                 }
                 Rule::intermediate_16_bit_command_no_reg => {
-                    let mut raw_intermediate = Vec::new();
-                    for char in pairs.as_str().chars() {
-                        if char == '0' {
-                            raw_intermediate.push(0);
-                        } else {
-                            raw_intermediate.push(1);
-                        }
-                    }
-
-                    let high_bits = (binary_to_int(&raw_intermediate[0..8]) as i16) << 8;
-                    let low_bits = (binary_to_int(&raw_intermediate[8..16]) as i16) & 255;
+                    let intermediate = parse_address(pairs.peek().unwrap(), &labels, address)?;
 
-                    let instruction =
-                        Instruction::Intermediate16BitNoReg(command, high_bits + low_bits);
+                    let instruction = Instruction::Intermediate16BitNoReg(command, intermediate);
                     instructions.push(instruction);
                 }
                 Rule::intermediate_command => {
-                    let mut intermediate = Vec::new();
-                    for char in pairs.as_str().chars() {
-                        if char == '0' {
-                            intermediate.push(0);
-                        } else {
-                            intermediate.push(1);
-                        }
-                    }
+                    let intermediate = parse_intermediate(pairs.peek().unwrap(), address)?;
 
-                    let instruction =
-                        Instruction::Intermediate(command, binary_to_int(&intermediate));
+                    let instruction = Instruction::Intermediate(command, intermediate);
                     instructions.push(instruction);
                 }
                 Rule::no_reg_command => {
@@ -185,47 +267,330 @@ pub fn parse(file_name: String) -> Vec<Instruction> {
                     instructions.push(instruction);
                 }
                 Rule::label_command => {
-                    let label = pairs.peek().unwrap().as_span().as_str();
-                    let instruction = Instruction::Label(command, *labels.get(label).unwrap());
+                    let target = resolve_expression(pairs.peek().unwrap(), &labels, address)?;
+
+                    let instruction = Instruction::Label(command, target);
+                    instructions.push(instruction);
+                }
+                Rule::restart_command => {
+                    // the grammar only allows a single digit from 0 to 7
+                    let number = pairs.peek().unwrap().as_str().parse().unwrap();
+
+                    let instruction = Instruction::Restart(command, number);
+                    instructions.push(instruction);
+                }
+                Rule::data_byte_command => {
+                    let instruction = Instruction::Data(command, parse_data_bytes(pairs, address)?);
                     instructions.push(instruction);
                 }
+                Rule::data_word_command => {
+                    let instruction =
+                        Instruction::Data(command, parse_data_words(pairs, &labels, address)?);
+                    instructions.push(instruction);
+                }
+                Rule::origin_command => {
+                    let origin = parse_intermediate_16_bit(pairs.peek().unwrap(), address)? as u16;
+                    instructions.push(Instruction::Origin(command, origin));
+                }
                 _ => panic!("invalid rule: {:?}", rule),
             }
+
+            match instructions.last() {
+                Some(Instruction::Origin(_, origin)) => address = *origin,
+                Some(instruction) => address = address.wrapping_add(instruction.get_size()),
+                None => (),
+            }
+        }
+    }
+    Ok((lines.into_iter().zip(instructions).collect(), labels))
+}
+
+// code following HLT can only be reached by a jump, which needs a label to jump to
+pub fn check_unreachable(source: &str) -> Result<Vec<Diagnostic>, ParseError> {
+    let assembly = AssemblyParser::parse(Rule::assembly, source)
+        .map_err(ParseError::from_syntax_error)?
+        .next()
+        .unwrap();
+
+    let mut diagnostics = Vec::new();
+    let mut halted = false;
+    for instruction in assembly.into_inner() {
+        match instruction.as_rule() {
+            Rule::label_definition => halted = false,
+            Rule::instruction => {
+                let mut pairs = instruction.into_inner();
+                if matches!(pairs.peek().unwrap().as_rule(), Rule::label) {
+                    halted = false;
+                    pairs.next();
+                }
+
+                let statement = pairs.next().unwrap();
+                let command = statement.clone().into_inner().next().unwrap();
+                match command.as_rule() {
+                    // data isn't executed and ORG starts a new section
+                    Rule::data_byte_command | Rule::data_word_command => continue,
+                    Rule::origin_command => {
+                        halted = false;
+                        continue;
+                    }
+                    _ => (),
+                }
+
+                if halted {
+                    diagnostics.push(Diagnostic::warning(
+                        statement.as_span(),
+                        "unreachable instruction after HLT",
+                    ));
+                }
+
+                halted = command.as_str().eq_ignore_ascii_case("HLT");
+            }
+            _ => (),
+        }
+    }
+
+    Ok(diagnostics)
+}
+
+// mnemonics and registers are case-insensitive, "mvi a" equals "MVI A", aliases aren't
+fn parse_register(
+    register: Pair<Rule>,
+    aliases: &HashMap<String, InstructionRegister>,
+) -> Result<InstructionRegister, ParseError> {
+    if matches!(register.as_rule(), Rule::register_alias) {
+        return match aliases.get(register.as_str()) {
+            Some(aliased) => Ok(*aliased),
+            None => Err(ParseError::new(
+                register.as_span(),
+                "unknown register alias",
+            )),
+        };
+    }
+
+    Ok(InstructionRegister::from_str(&register.as_str().to_uppercase()).unwrap())
+}
+
+// pairs are named after their first register, the grammar only allows B, D, H, SP and PSW
+fn parse_register_pair(register_pair: Pair<Rule>) -> InstructionRegisterPair {
+    match register_pair.as_str().to_uppercase().as_str() {
+        "B" => InstructionRegisterPair::BC,
+        "D" => InstructionRegisterPair::DE,
+        "H" => InstructionRegisterPair::HL,
+        "SP" => InstructionRegisterPair::SP,
+        "PSW" => InstructionRegisterPair::FA,
+        name => panic!("invalid register pair: {}", name),
+    }
+}
+
+// STAX and LDAX only work with B and D, PUSH and POP use PSW instead of SP
+fn validate_register_pair(
+    command: &InstructionCommand,
+    register_pair: &InstructionRegisterPair,
+    operand: Pair<Rule>,
+) -> Result<(), ParseError> {
+    let valid = match command {
+        InstructionCommand::Stax | InstructionCommand::Ldax => matches!(
+            register_pair,
+            InstructionRegisterPair::BC | InstructionRegisterPair::DE
+        ),
+        InstructionCommand::Push | InstructionCommand::Pop => {
+            !matches!(register_pair, InstructionRegisterPair::SP)
+        }
+        _ => !matches!(register_pair, InstructionRegisterPair::FA),
+    };
+
+    if !valid {
+        return Err(ParseError::new(
+            operand.as_span(),
+            &format!("invalid register pair for {}", command),
+        ));
+    }
+
+    Ok(())
+}
+
+fn resolve_label(label: Pair<Rule>, labels: &HashMap<String, u16>) -> Result<u16, ParseError> {
+    match labels.get(label.as_str()) {
+        Some(address) => Ok(*address),
+        None => Err(ParseError::new(label.as_span(), "unknown label")),
+    }
+}
+
+// a label or $, optionally moved by a constant, has to stay within the address space
+fn resolve_expression(
+    expression: Pair<Rule>,
+    labels: &HashMap<String, u16>,
+    current_address: u16,
+) -> Result<u16, ParseError> {
+    let span = expression.as_span();
+    let mut pairs = expression.into_inner();
+
+    let symbol = pairs.next().unwrap();
+    let base = match symbol.as_rule() {
+        Rule::current_address => current_address,
+        _ => resolve_label(symbol, labels)?,
+    };
+
+    let offset = match pairs.next() {
+        Some(offset) => {
+            let mut pairs = offset.into_inner();
+            let sign = pairs.next().unwrap();
+            let value = parse_number(pairs.next().unwrap(), current_address);
+
+            match (sign.as_str(), value) {
+                ("-", Some(value)) => -value,
+                (_, Some(value)) => value,
+                (_, None) => return Err(ParseError::new(span, "address out of range")),
+            }
+        }
+        None => 0,
+    };
+
+    match base as i64 + offset {
+        address if (0..=65535).contains(&address) => Ok(address as u16),
+        _ => Err(ParseError::new(span, "address out of range")),
+    }
+}
+
+// accepts binary (00011100), hex (0x1C or 1CH) and decimal (28 or -100)
+// values, anything from -128 up to 255 fits into one byte
+// the byte is stored either way, so signed (-128..=127) and unsigned (0..=255) immediates are
+// accepted for every instruction, ADI 200 adds the same byte as ADI -56
+fn parse_intermediate(intermediate: Pair<Rule>, current_address: u16) -> Result<i8, ParseError> {
+    let span = intermediate.as_span();
+
+    match parse_number(intermediate, current_address) {
+        Some(value) if (-128..=255).contains(&value) => Ok(value as i8),
+        _ => Err(ParseError::new(span, "intermediate out of range")),
+    }
+}
+
+// same as parse_intermediate, anything from -32768 up to 65535 fits into two bytes
+fn parse_intermediate_16_bit(
+    intermediate: Pair<Rule>,
+    current_address: u16,
+) -> Result<i16, ParseError> {
+    let span = intermediate.as_span();
+
+    match parse_number(intermediate, current_address) {
+        Some(value) if (-32768..=65535).contains(&value) => Ok(value as i16),
+        _ => Err(ParseError::new(span, "intermediate out of range")),
+    }
+}
+
+// addresses can be given as a number or as a label
+fn parse_address(
+    address: Pair<Rule>,
+    labels: &HashMap<String, u16>,
+    current_address: u16,
+) -> Result<i16, ParseError> {
+    if matches!(address.as_rule(), Rule::label_expression) {
+        return Ok(resolve_expression(address, labels, current_address)? as i16);
+    }
+
+    parse_intermediate_16_bit(address, current_address)
+}
+
+// strings are emitted as one byte per character, e.g. 'AB' -> 65, 66
+fn parse_data_bytes(data_bytes: Pairs<Rule>, current_address: u16) -> Result<Vec<i8>, ParseError> {
+    let mut bytes = Vec::new();
+
+    for data_byte in data_bytes {
+        let value = data_byte.into_inner().next().unwrap();
+
+        if matches!(value.as_rule(), Rule::string) {
+            if !value.as_str().is_ascii() {
+                return Err(ParseError::new(
+                    value.as_span(),
+                    "invalid character in string",
+                ));
+            }
+
+            for char in value.as_str().trim_matches('\'').chars() {
+                bytes.push(char as u8 as i8);
+            }
+        } else {
+            bytes.push(parse_intermediate(value, current_address)?);
         }
     }
-    instructions
+
+    Ok(bytes)
+}
+
+// words are stored little-endian, e.g. 0x1234 -> 0x34, 0x12
+fn parse_data_words(
+    data_words: Pairs<Rule>,
+    labels: &HashMap<String, u16>,
+    current_address: u16,
+) -> Result<Vec<i8>, ParseError> {
+    let mut bytes = Vec::new();
+
+    for data_word in data_words {
+        let word = parse_address(
+            data_word.into_inner().next().unwrap(),
+            labels,
+            current_address,
+        )?;
+
+        bytes.push((word & 255) as i8);
+        bytes.push((word >> 8) as i8);
+    }
+
+    Ok(bytes)
 }
 
-fn parse_labels(raw_instructions: Pairs<Rule>) -> HashMap<String, u16> {
+// $ stands for the address of the instruction it's used in
+fn parse_number(intermediate: Pair<Rule>, current_address: u16) -> Option<i64> {
+    let number = intermediate.into_inner().next().unwrap();
+    let text = number.as_str();
+
+    let value = match number.as_rule() {
+        Rule::binary_intermediate | Rule::binary_intermediate_16_bit => {
+            i64::from_str_radix(text, 2)
+        }
+        Rule::hex_intermediate => {
+            let digits = text
+                .trim_start_matches("0x")
+                .trim_start_matches("0X")
+                .trim_end_matches(['H', 'h']);
+            i64::from_str_radix(digits, 16)
+        }
+        Rule::decimal_intermediate | Rule::offset_decimal => text.parse::<i64>(),
+        Rule::current_address => Ok(current_address as i64),
+        _ => panic!("invalid intermediate: {:?}", text),
+    };
+
+    value.ok()
+}
+
+fn parse_labels(raw_instructions: Pairs<Rule>) -> Result<HashMap<String, u16>, ParseError> {
     let mut labels = HashMap::new();
-    let mut label_address = 0;
+    // wraps around like the program counter, e.g. for code right below 0xFFFF
+    let mut label_address: u16 = 0;
 
-    let mut label = "".to_owned();
+    // labels waiting for the next instruction, a label on its own line belongs to it
+    let mut pending_labels = Vec::new();
     for instruction in raw_instructions {
         let rule = instruction.as_rule();
 
-        // ignore comments and end of input
-        if !matches!(rule, Rule::comment | Rule::EOI) {
+        if matches!(rule, Rule::label_definition) {
+            let label = instruction.into_inner().next().unwrap();
+            pending_labels.push(check_label(label, &labels, &pending_labels)?);
+            continue;
+        }
+
+        // ignore comments, aliases, computed calls and end of input
+        if !matches!(
+            rule,
+            Rule::comment | Rule::equ_definition | Rule::computed_call | Rule::EOI
+        ) {
             let mut inner_instruction_pairs = instruction.into_inner();
             let inner_instruction = inner_instruction_pairs.peek().unwrap();
 
             let mut rule = inner_instruction.as_rule();
 
             if matches!(rule, Rule::label) {
-                label = inner_instruction.as_str().to_string();
-
-                for value in labels.keys() {
-                    if label == *value {
-                        panic!("can't have duplicate labels: {:?}", label);
-                    }
-                }
-
-                if InstructionCommand::from_str(&label).is_ok()
-                    || InstructionRegister::from_str(&label).is_ok()
-                {
-                    panic!("label can't occupy reserved names: {:?}", label);
-                }
-
+                pending_labels.push(check_label(inner_instruction, &labels, &pending_labels)?);
                 inner_instruction_pairs.next();
             }
 
@@ -234,49 +599,134 @@ fn parse_labels(raw_instructions: Pairs<Rule>) -> HashMap<String, u16> {
             rule = inner_instruction.as_rule();
             pairs.next();
 
-            if !label.is_empty() {
-                labels.insert(label.trim_end_matches(':').to_owned(), label_address);
-                label = "".to_owned();
+            for label in pending_labels.drain(..) {
+                labels.insert(label, label_address);
             }
 
             match rule {
                 Rule::intermediate_reg_command => {
-                    label_address += 2;
+                    label_address = label_address.wrapping_add(2);
                 }
                 Rule::intermediate_16_bit_command => {
-                    label_address += 3;
+                    label_address = label_address.wrapping_add(3);
                 }
                 Rule::double_reg_command => {
-                    label_address += 1;
+                    label_address = label_address.wrapping_add(1);
                 }
                 Rule::single_reg_command => {
-                    label_address += 1;
+                    label_address = label_address.wrapping_add(1);
                 }
                 Rule::pair_reg_command => {
-                    label_address += 1;
+                    label_address = label_address.wrapping_add(1);
                 }
                 Rule::intermediate_16_bit_command_no_reg => {
-                    label_address += 3;
+                    label_address = label_address.wrapping_add(3);
                 }
                 Rule::intermediate_command => {
-                    label_address += 2;
+                    label_address = label_address.wrapping_add(2);
                 }
                 Rule::no_reg_command => {
-                    label_address += 1;
+                    label_address = label_address.wrapping_add(1);
                 }
                 Rule::label_command => {
-                    label_address += 3;
+                    label_address = label_address.wrapping_add(3);
+                }
+                Rule::restart_command => {
+                    label_address = label_address.wrapping_add(1);
+                }
+                Rule::data_byte_command => {
+                    let size = parse_data_bytes(pairs, label_address)?.len() as u16;
+                    label_address = label_address.wrapping_add(size);
+                }
+                Rule::data_word_command => {
+                    label_address = label_address.wrapping_add(2 * pairs.count() as u16);
+                }
+                Rule::origin_command => {
+                    label_address =
+                        parse_intermediate_16_bit(pairs.peek().unwrap(), label_address)? as u16;
                 }
                 _ => panic!("invalid rule: {:?}", rule),
             }
         }
     }
 
-    labels
+    // labels at the end of the source point right behind the last instruction
+    for label in pending_labels {
+        labels.insert(label, label_address);
+    }
+
+    Ok(labels)
+}
+
+// registers bound to a name with EQU, the names can't be used as labels as well
+fn parse_aliases(
+    raw_instructions: Pairs<Rule>,
+    labels: &HashMap<String, u16>,
+) -> Result<HashMap<String, InstructionRegister>, ParseError> {
+    let mut aliases = HashMap::new();
+
+    for instruction in raw_instructions {
+        if !matches!(instruction.as_rule(), Rule::equ_definition) {
+            continue;
+        }
+
+        let mut pairs = instruction.into_inner();
+        // the name is atomic together with EQU, the error only points at the name
+        let definition = pairs.next().unwrap().as_span();
+        let name = definition.as_str().split_whitespace().next().unwrap();
+        let span = definition.get(..name.len()).unwrap();
+        let register = parse_register(pairs.next().unwrap(), &aliases)?;
+
+        let message = if aliases.contains_key(name) {
+            Some("duplicate register alias")
+        } else if labels.contains_key(name) {
+            Some("register alias collides with a label")
+        } else if InstructionCommand::from_str(&name.to_uppercase()).is_ok()
+            || InstructionRegister::from_str(&name.to_uppercase()).is_ok()
+            || matches!(name.to_uppercase().as_str(), "SP" | "PSW" | "EQU")
+        {
+            Some("register alias can't occupy reserved names")
+        } else {
+            None
+        };
+
+        if let Some(message) = message {
+            return Err(ParseError::new(span, message));
+        }
+
+        aliases.insert(name.to_owned(), register);
+    }
+
+    Ok(aliases)
+}
+
+fn check_label(
+    label: Pair<Rule>,
+    labels: &HashMap<String, u16>,
+    pending_labels: &[String],
+) -> Result<String, ParseError> {
+    let name = label.as_str().trim_end_matches(':');
+
+    if labels.contains_key(name) || pending_labels.iter().any(|pending| pending == name) {
+        return Err(ParseError::new(label.as_span(), "duplicate label"));
+    }
+
+    if InstructionCommand::from_str(&name.to_uppercase()).is_ok()
+        || InstructionRegister::from_str(&name.to_uppercase()).is_ok()
+    {
+        return Err(ParseError::new(
+            label.as_span(),
+            "label can't occupy reserved names",
+        ));
+    }
+
+    Ok(name.to_owned())
 }
 
-#[derive(Debug, EnumString, Clone, PartialEq)]
+#[derive(Debug, Display, EnumString, Clone, PartialEq)]
 pub enum InstructionCommand {
+    #[strum(serialize = "NOP")]
+    Nop,
     #[strum(serialize = "MVI")]
     Mvi,
     #[strum(serialize = "MOV")]
@@ -383,8 +833,66 @@ pub enum InstructionCommand {
     Jpe,
     #[strum(serialize = "JPO")]
     Jpo,
+    #[strum(serialize = "CALL")]
+    Call,
+    #[strum(serialize = "CC")]
+    Cc,
+    #[strum(serialize = "CNC")]
+    Cnc,
+    #[strum(serialize = "CZ")]
+    Cz,
+    #[strum(serialize = "CNZ")]
+    Cnz,
+    #[strum(serialize = "CM")]
+    Cm,
+    #[strum(serialize = "CP")]
+    Cp,
+    #[strum(serialize = "CPE")]
+    Cpe,
+    #[strum(serialize = "CPO")]
+    Cpo,
+    #[strum(serialize = "RET")]
+    Ret,
+    #[strum(serialize = "RC")]
+    Rc,
+    #[strum(serialize = "RNC")]
+    Rnc,
+    #[strum(serialize = "RZ")]
+    Rz,
+    #[strum(serialize = "RNZ")]
+    Rnz,
+    #[strum(serialize = "RM")]
+    Rm,
+    #[strum(serialize = "RP")]
+    Rp,
+    #[strum(serialize = "RPE")]
+    Rpe,
+    #[strum(serialize = "RPO")]
+    Rpo,
+    #[strum(serialize = "RST")]
+    Rst,
     #[strum(serialize = "HLT")]
     Hlt,
+    #[strum(serialize = "IN")]
+    In,
+    #[strum(serialize = "OUT")]
+    Out,
+    #[strum(serialize = "EI")]
+    Ei,
+    #[strum(serialize = "DI")]
+    Di,
+    #[cfg(feature = "i8085")]
+    #[strum(serialize = "RIM")]
+    Rim,
+    #[cfg(feature = "i8085")]
+    #[strum(serialize = "SIM")]
+    Sim,
+    #[strum(serialize = "DB")]
+    Db,
+    #[strum(serialize = "DW")]
+    Dw,
+    #[strum(serialize = "ORG")]
+    Org,
 }
 
 pub trait InstructionArgument {
@@ -392,7 +900,7 @@ pub trait InstructionArgument {
     fn decode(raw_bits: &[u8]) -> Self;
 }
 
-#[derive(Debug, Copy, Clone, EnumString, PartialEq)]
+#[derive(Debug, Copy, Clone, Display, EnumString, PartialEq)]
 pub enum InstructionRegister {
     A,
     B,
@@ -462,7 +970,7 @@ impl InstructionRegister {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum InstructionRegisterPair {
     BC,
     DE,
@@ -493,6 +1001,33 @@ impl InstructionArgument for InstructionRegisterPair {
     }
 }
 
+impl InstructionRegisterPair {
+    // 11 is SP for most instructions, PUSH and POP use it for PSW instead
+    pub fn decode_for(command: &InstructionCommand, raw_bits: &[u8]) -> InstructionRegisterPair {
+        match (command, raw_bits) {
+            (InstructionCommand::Push | InstructionCommand::Pop, [1, 1]) => {
+                InstructionRegisterPair::FA
+            }
+            _ => InstructionRegisterPair::decode(raw_bits),
+        }
+    }
+}
+
+// register pairs are named after their first register in assembly
+impl fmt::Display for InstructionRegisterPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            InstructionRegisterPair::BC => "B",
+            InstructionRegisterPair::DE => "D",
+            InstructionRegisterPair::HL => "H",
+            InstructionRegisterPair::SP => "SP",
+            InstructionRegisterPair::FA => "PSW",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
 impl InstructionRegisterPair {
     pub fn get_registers(&self) -> (InstructionRegister, InstructionRegister) {
         match self {
@@ -518,10 +1053,65 @@ pub enum Instruction {
     IntermediateRegister(InstructionCommand, i8, InstructionRegister),
     PairRegister(InstructionCommand, InstructionRegisterPair),
     Label(InstructionCommand, u16),
+    // number of the restart, RST n calls 8 * n
+    Restart(InstructionCommand, u8),
+    Data(InstructionCommand, Vec<i8>),
+    Origin(InstructionCommand, u16),
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::NoRegister(command) => write!(f, "{}", command),
+            Instruction::SingleRegister(command, register) => {
+                write!(f, "{} {}", command, register)
+            }
+            Instruction::DoubleRegister(command, (register0, register1)) => {
+                write!(f, "{} {}, {}", command, register0, register1)
+            }
+            Instruction::Intermediate(command, intermediate) => {
+                write!(f, "{} 0x{:02X}", command, *intermediate as u8)
+            }
+            Instruction::Intermediate16Bit(command, register_pair, intermediate) => {
+                write!(
+                    f,
+                    "{} {}, 0x{:04X}",
+                    command, register_pair, *intermediate as u16
+                )
+            }
+            Instruction::Intermediate16BitNoReg(command, intermediate) => {
+                write!(f, "{} 0x{:04X}", command, *intermediate as u16)
+            }
+            Instruction::IntermediateRegister(command, intermediate, register) => {
+                write!(f, "{} {}, 0x{:02X}", command, register, *intermediate as u8)
+            }
+            Instruction::PairRegister(command, register_pair) => {
+                write!(f, "{} {}", command, register_pair)
+            }
+            Instruction::Label(command, address) => write!(f, "{} 0x{:04X}", command, address),
+            Instruction::Restart(command, number) => write!(f, "{} {}", command, number),
+            Instruction::Data(command, bytes) => {
+                // words are stored little-endian
+                let values: Vec<String> = match command {
+                    InstructionCommand::Dw => bytes
+                        .chunks(2)
+                        .map(|word| format!("0x{:02X}{:02X}", word[1] as u8, word[0] as u8))
+                        .collect(),
+                    _ => bytes
+                        .iter()
+                        .map(|byte| format!("0x{:02X}", *byte as u8))
+                        .collect(),
+                };
+
+                write!(f, "{} {}", command, values.join(", "))
+            }
+            Instruction::Origin(command, address) => write!(f, "{} 0x{:04X}", command, address),
+        }
+    }
 }
 
 impl Instruction {
-    pub fn get_size(&self) -> u8 {
+    pub fn get_size(&self) -> u16 {
         match self {
             Instruction::NoRegister(_) => 1,
             Instruction::SingleRegister(_, _) => 1,
@@ -532,12 +1122,18 @@ impl Instruction {
             Instruction::IntermediateRegister(_, _, _) => 2,
             Instruction::PairRegister(_, _) => 1,
             Instruction::Label(_, _) => 3,
+            Instruction::Restart(_, _) => 1,
+            Instruction::Data(_, bytes) => bytes.len() as u16,
+            Instruction::Origin(_, _) => 0,
         }
     }
 
     pub fn encode(&self) -> Vec<u8> {
         match self {
             Instruction::NoRegister(command) => match command {
+                InstructionCommand::Nop => {
+                    vec![0, 0, 0, 0, 0, 0, 0, 0]
+                }
                 InstructionCommand::Stc => {
                     vec![0, 0, 1, 1, 0, 1, 1, 1]
                 }
@@ -574,9 +1170,50 @@ impl Instruction {
                 InstructionCommand::Pchl => {
                     vec![1, 1, 1, 0, 1, 0, 0, 1]
                 }
+                InstructionCommand::Ret => {
+                    vec![1, 1, 0, 0, 1, 0, 0, 1]
+                }
+                InstructionCommand::Rc => {
+                    vec![1, 1, 0, 1, 1, 0, 0, 0]
+                }
+                InstructionCommand::Rnc => {
+                    vec![1, 1, 0, 1, 0, 0, 0, 0]
+                }
+                InstructionCommand::Rz => {
+                    vec![1, 1, 0, 0, 1, 0, 0, 0]
+                }
+                InstructionCommand::Rnz => {
+                    vec![1, 1, 0, 0, 0, 0, 0, 0]
+                }
+                InstructionCommand::Rm => {
+                    vec![1, 1, 1, 1, 1, 0, 0, 0]
+                }
+                InstructionCommand::Rp => {
+                    vec![1, 1, 1, 1, 0, 0, 0, 0]
+                }
+                InstructionCommand::Rpe => {
+                    vec![1, 1, 1, 0, 1, 0, 0, 0]
+                }
+                InstructionCommand::Rpo => {
+                    vec![1, 1, 1, 0, 0, 0, 0, 0]
+                }
                 InstructionCommand::Hlt => {
                     vec![0, 1, 1, 1, 0, 1, 1, 0]
                 }
+                InstructionCommand::Ei => {
+                    vec![1, 1, 1, 1, 1, 0, 1, 1]
+                }
+                InstructionCommand::Di => {
+                    vec![1, 1, 1, 1, 0, 0, 1, 1]
+                }
+                #[cfg(feature = "i8085")]
+                InstructionCommand::Rim => {
+                    vec![0, 0, 1, 0, 0, 0, 0, 0]
+                }
+                #[cfg(feature = "i8085")]
+                InstructionCommand::Sim => {
+                    vec![0, 0, 1, 1, 0, 0, 0, 0]
+                }
                 _ => panic!("invalid instruction"),
             },
 
@@ -648,6 +1285,11 @@ impl Instruction {
 
             Instruction::DoubleRegister(command, registers) => match command {
                 InstructionCommand::Mov => {
+                    assert!(
+                        *registers != (InstructionRegister::M, InstructionRegister::M),
+                        "MOV M, M can't be encoded"
+                    );
+
                     let mut base_result = vec![0, 1];
                     base_result.append(&mut registers.0.encode());
                     base_result.append(&mut registers.1.encode());
@@ -706,6 +1348,18 @@ impl Instruction {
 
                     base_result
                 }
+                InstructionCommand::In => {
+                    let mut base_result = vec![1, 1, 0, 1, 1, 0, 1, 1];
+                    base_result.append(&mut int_to_binary(*intermediate as i16, 8));
+
+                    base_result
+                }
+                InstructionCommand::Out => {
+                    let mut base_result = vec![1, 1, 0, 1, 0, 0, 1, 1];
+                    base_result.append(&mut int_to_binary(*intermediate as i16, 8));
+
+                    base_result
+                }
                 _ => panic!("invalid instruction"),
             },
 
@@ -714,7 +1368,7 @@ impl Instruction {
                     let mut base_result = vec![0, 0];
                     base_result.append(&mut register_pair.encode());
                     base_result.append(&mut vec![0, 0, 0, 1]);
-                    base_result.append(&mut int_to_binary(*intermediate, 16));
+                    base_result.append(&mut word_to_binary(*intermediate as u16));
 
                     base_result
                 }
@@ -724,25 +1378,25 @@ impl Instruction {
             Instruction::Intermediate16BitNoReg(command, intermediate) => match command {
                 InstructionCommand::Sta => {
                     let mut base_result = vec![0, 0, 1, 1, 0, 0, 1, 0];
-                    base_result.append(&mut int_to_binary(*intermediate, 16));
+                    base_result.append(&mut word_to_binary(*intermediate as u16));
 
                     base_result
                 }
                 InstructionCommand::Lda => {
                     let mut base_result = vec![0, 0, 1, 1, 1, 0, 1, 0];
-                    base_result.append(&mut int_to_binary(*intermediate, 16));
+                    base_result.append(&mut word_to_binary(*intermediate as u16));
 
                     base_result
                 }
                 InstructionCommand::Shld => {
                     let mut base_result = vec![0, 0, 1, 0, 0, 0, 1, 0];
-                    base_result.append(&mut int_to_binary(*intermediate, 16));
+                    base_result.append(&mut word_to_binary(*intermediate as u16));
 
                     base_result
                 }
                 InstructionCommand::Lhld => {
                     let mut base_result = vec![0, 0, 1, 0, 1, 0, 1, 0];
-                    base_result.append(&mut int_to_binary(*intermediate, 16));
+                    base_result.append(&mut word_to_binary(*intermediate as u16));
 
                     base_result
                 }
@@ -766,98 +1420,152 @@ impl Instruction {
                 match command {
                     InstructionCommand::Jmp => {
                         base_result.append(&mut vec![1, 1, 0, 0, 0, 0, 1, 1]);
-                        base_result.append(&mut int_to_binary(*address as i16, 16));
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
                     InstructionCommand::Jc => {
                         base_result.append(&mut vec![1, 1, 0, 1, 1, 0, 1, 0]);
-                        base_result.append(&mut int_to_binary(*address as i16, 16));
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
                     InstructionCommand::Jnc => {
                         base_result.append(&mut vec![1, 1, 0, 1, 0, 0, 1, 0]);
-                        base_result.append(&mut int_to_binary(*address as i16, 16));
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
                     InstructionCommand::Jz => {
                         base_result.append(&mut vec![1, 1, 0, 0, 1, 0, 1, 0]);
-                        base_result.append(&mut int_to_binary(*address as i16, 16));
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
                     InstructionCommand::Jnz => {
                         base_result.append(&mut vec![1, 1, 0, 0, 0, 0, 1, 0]);
-                        base_result.append(&mut int_to_binary(*address as i16, 16));
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
                     InstructionCommand::Jm => {
                         base_result.append(&mut vec![1, 1, 1, 1, 1, 0, 1, 0]);
-                        base_result.append(&mut int_to_binary(*address as i16, 16));
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
                     InstructionCommand::Jp => {
                         base_result.append(&mut vec![1, 1, 1, 1, 0, 0, 1, 0]);
-                        base_result.append(&mut int_to_binary(*address as i16, 16));
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
                     InstructionCommand::Jpe => {
                         base_result.append(&mut vec![1, 1, 1, 0, 1, 0, 1, 0]);
-                        base_result.append(&mut int_to_binary(*address as i16, 16));
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
                     InstructionCommand::Jpo => {
                         base_result.append(&mut vec![1, 1, 1, 0, 0, 0, 1, 0]);
-                        base_result.append(&mut int_to_binary(*address as i16, 16));
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
-                    _ => panic!("invalid instruction"),
-                }
-            }
+                    InstructionCommand::Call => {
+                        base_result.append(&mut vec![1, 1, 0, 0, 1, 1, 0, 1]);
+                        base_result.append(&mut word_to_binary(*address));
 
-            Instruction::PairRegister(command, register_pair) => {
-                let mut base_result = vec![0, 0];
-                match command {
-                    InstructionCommand::Stax => {
-                        base_result.append(&mut register_pair.encode());
-                        base_result.append(&mut vec![0, 0, 1, 0]);
+                        base_result
+                    }
+                    InstructionCommand::Cc => {
+                        base_result.append(&mut vec![1, 1, 0, 1, 1, 1, 0, 0]);
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
-                    InstructionCommand::Ldax => {
-                        base_result.append(&mut register_pair.encode());
-                        base_result.append(&mut vec![1, 0, 1, 0]);
+                    InstructionCommand::Cnc => {
+                        base_result.append(&mut vec![1, 1, 0, 1, 0, 1, 0, 0]);
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
-                    InstructionCommand::Dcx => {
-                        base_result.append(&mut register_pair.encode());
-                        base_result.append(&mut vec![1, 0, 1, 1]);
+                    InstructionCommand::Cz => {
+                        base_result.append(&mut vec![1, 1, 0, 0, 1, 1, 0, 0]);
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
-                    InstructionCommand::Inx => {
-                        base_result.append(&mut register_pair.encode());
-                        base_result.append(&mut vec![0, 0, 1, 1]);
+                    InstructionCommand::Cnz => {
+                        base_result.append(&mut vec![1, 1, 0, 0, 0, 1, 0, 0]);
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
-                    InstructionCommand::Dad => {
-                        base_result.append(&mut register_pair.encode());
-                        base_result.append(&mut vec![1, 0, 0, 1]);
+                    InstructionCommand::Cm => {
+                        base_result.append(&mut vec![1, 1, 1, 1, 1, 1, 0, 0]);
+                        base_result.append(&mut word_to_binary(*address));
 
                         base_result
                     }
-                    InstructionCommand::Push => {
-                        base_result = vec![1, 1];
-                        if matches!(register_pair, InstructionRegisterPair::SP) {
+                    InstructionCommand::Cp => {
+                        base_result.append(&mut vec![1, 1, 1, 1, 0, 1, 0, 0]);
+                        base_result.append(&mut word_to_binary(*address));
+
+                        base_result
+                    }
+                    InstructionCommand::Cpe => {
+                        base_result.append(&mut vec![1, 1, 1, 0, 1, 1, 0, 0]);
+                        base_result.append(&mut word_to_binary(*address));
+
+                        base_result
+                    }
+                    InstructionCommand::Cpo => {
+                        base_result.append(&mut vec![1, 1, 1, 0, 0, 1, 0, 0]);
+                        base_result.append(&mut word_to_binary(*address));
+
+                        base_result
+                    }
+                    _ => panic!("invalid instruction"),
+                }
+            }
+
+            Instruction::PairRegister(command, register_pair) => {
+                let mut base_result = vec![0, 0];
+                match command {
+                    InstructionCommand::Stax => {
+                        base_result.append(&mut register_pair.encode());
+                        base_result.append(&mut vec![0, 0, 1, 0]);
+
+                        base_result
+                    }
+                    InstructionCommand::Ldax => {
+                        base_result.append(&mut register_pair.encode());
+                        base_result.append(&mut vec![1, 0, 1, 0]);
+
+                        base_result
+                    }
+                    InstructionCommand::Dcx => {
+                        base_result.append(&mut register_pair.encode());
+                        base_result.append(&mut vec![1, 0, 1, 1]);
+
+                        base_result
+                    }
+                    InstructionCommand::Inx => {
+                        base_result.append(&mut register_pair.encode());
+                        base_result.append(&mut vec![0, 0, 1, 1]);
+
+                        base_result
+                    }
+                    InstructionCommand::Dad => {
+                        base_result.append(&mut register_pair.encode());
+                        base_result.append(&mut vec![1, 0, 0, 1]);
+
+                        base_result
+                    }
+                    InstructionCommand::Push => {
+                        base_result = vec![1, 1];
+                        if matches!(register_pair, InstructionRegisterPair::SP) {
                             panic!("can not use SP in this instruction");
                         }
                         base_result.append(&mut register_pair.encode());
@@ -878,6 +1586,47 @@ impl Instruction {
                     _ => panic!("invalid instruction"),
                 }
             }
+
+            Instruction::Restart(command, number) => match command {
+                InstructionCommand::Rst => {
+                    let mut base_result = vec![1, 1];
+                    base_result.append(&mut int_to_binary(*number as i16, 3));
+                    base_result.append(&mut vec![1, 1, 1]);
+
+                    base_result
+                }
+                _ => panic!("invalid instruction"),
+            },
+
+            Instruction::Data(command, bytes) => match command {
+                InstructionCommand::Db | InstructionCommand::Dw => {
+                    let mut base_result = vec![];
+                    for byte in bytes {
+                        base_result.append(&mut int_to_binary(*byte as i16, 8));
+                    }
+
+                    base_result
+                }
+                _ => panic!("invalid instruction"),
+            },
+
+            // the gap up to the new origin is filled in by the assembler
+            Instruction::Origin(_, _) => vec![],
+        }
+    }
+
+    // equal if both encode to the same bytes, no matter if an immediate is stored as -1 or 0xFF
+    pub fn semantic_eq(&self, other: &Instruction) -> bool {
+        match (self, other) {
+            // ORG doesn't encode to anything
+            (Instruction::Origin(_, origin), Instruction::Origin(_, other_origin)) => {
+                origin == other_origin
+            }
+            // DB 0x76 has the same bytes as HLT, but isn't an instruction
+            _ => {
+                mem::discriminant(self) == mem::discriminant(other)
+                    && self.encode() == other.encode()
+            }
         }
     }
 }
@@ -894,27 +1643,35 @@ pub fn int_to_binary(value: i16, mut size: i8) -> Vec<u8> {
     result
 }
 
-pub fn binary_to_int(intermediate: &[u8]) -> i8 {
-    let mut result = 0;
+// 16 bit operands are stored little-endian like on the 8080, the low byte comes first
+fn word_to_binary(value: u16) -> Vec<u8> {
+    let mut result = int_to_binary((value & 255) as i16, 8);
+    result.append(&mut int_to_binary((value >> 8) as i16, 8));
 
-    for (i, num) in intermediate.iter().enumerate() {
-        result |= num;
+    result
+}
 
-        if i != 7 {
-            result <<= 1;
-        }
-    }
+pub fn binary_to_int(intermediate: &[u8]) -> i8 {
+    binary_to_int_16(intermediate) as i8
+}
 
-    result as i8
+// inverse of int_to_binary(value, 16), the first bit is the most significant one
+pub fn binary_to_int_16(intermediate: &[u8]) -> i16 {
+    intermediate
+        .iter()
+        .fold(0u16, |result, bit| (result << 1) | *bit as u16) as i16
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::assembler::parser::binary_to_int;
     use crate::assembler::parser::int_to_binary;
+    use crate::assembler::parser::{binary_to_int, binary_to_int_16};
 
-    use super::parse;
-    use super::{InstructionArgument, InstructionRegister, InstructionRegisterPair};
+    use super::{parse, parse_str};
+    use super::{
+        Instruction, InstructionArgument, InstructionCommand, InstructionRegister,
+        InstructionRegisterPair,
+    };
 
     #[test]
     fn test_register_encoding() {
@@ -1053,8 +1810,42 @@ mod tests {
 
     #[test]
     fn test_binary_to_int() {
-        assert_eq!(binary_to_int(&mut vec![0, 0, 0, 0, 1, 1, 1, 1]), 15);
-        assert_eq!(binary_to_int(&mut vec![1, 0, 0, 0, 0, 0, 0, 0]), -128);
+        assert_eq!(binary_to_int(&[0, 0, 0, 0, 1, 1, 1, 1]), 15);
+        assert_eq!(binary_to_int(&[1, 0, 0, 0, 0, 0, 0, 0]), -128);
+    }
+
+    #[test]
+    fn test_binary_to_int_16() {
+        assert_eq!(binary_to_int_16(&int_to_binary(4000, 16)), 4000);
+        assert_eq!(binary_to_int_16(&[1; 16]), -1);
+        assert_eq!(int_to_binary(-1, 16), vec![1; 16]);
+
+        let mut min = vec![0; 16];
+        min[0] = 1;
+        assert_eq!(int_to_binary(-32768, 16), min);
+        assert_eq!(binary_to_int_16(&min), -32768);
+    }
+
+    #[test]
+    fn test_lxi_negative_round_trip() {
+        for value in [-1, -32768] {
+            let instruction = Instruction::Intermediate16Bit(
+                InstructionCommand::Lxi,
+                InstructionRegisterPair::BC,
+                value,
+            );
+            let encoding = instruction.encode();
+            // low byte first
+            let mut word = encoding[16..].to_vec();
+            word.extend_from_slice(&encoding[8..16]);
+            assert_eq!(binary_to_int_16(&word), value);
+
+            let bytes: Vec<u8> = encoding
+                .chunks(8)
+                .map(|bits| binary_to_int(bits) as u8)
+                .collect();
+            assert_eq!(Instruction::decode_one(&bytes), Ok((instruction, 3)));
+        }
     }
 
     #[test]
@@ -1068,8 +1859,628 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn test_duplicate_labels() {
-        parse("data/test/duplicate_labels.asm".to_string());
+        let error = parse("data/test/duplicate_labels.asm".to_string()).unwrap_err();
+
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "duplicate label");
+        assert_eq!(error.text, "TEST:");
+    }
+
+    #[test]
+    fn test_backward_jump() {
+        let instructions = parse("data/test/backward_jump.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions[2],
+            Instruction::Label(InstructionCommand::Jmp, 2)
+        );
+        assert_eq!(
+            instructions[2].encode(),
+            vec![1, 1, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_subroutine() {
+        let instructions = parse("data/test/subroutine.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions[2],
+            Instruction::Label(InstructionCommand::Call, 23)
+        );
+        assert_eq!(
+            instructions[2].encode(),
+            vec![1, 1, 0, 0, 1, 1, 0, 1, 0, 0, 0, 1, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        // CPI is an immediate, CP a conditional call
+        assert_eq!(
+            instructions[3],
+            Instruction::Intermediate(InstructionCommand::Cpi, 2)
+        );
+        assert_eq!(
+            instructions[7],
+            Instruction::Label(InstructionCommand::Cp, 23)
+        );
+        assert_eq!(
+            instructions[10],
+            Instruction::NoRegister(InstructionCommand::Rnc)
+        );
+        assert_eq!(instructions[10].encode(), vec![1, 1, 0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_restart() {
+        let instructions = parse_str("RST 0\nrst 7\nRPE\nRP\n").unwrap();
+
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::Restart(InstructionCommand::Rst, 0),
+                Instruction::Restart(InstructionCommand::Rst, 7),
+                Instruction::NoRegister(InstructionCommand::Rpe),
+                Instruction::NoRegister(InstructionCommand::Rp),
+            ]
+        );
+        assert_eq!(instructions[1].encode(), vec![1, 1, 1, 1, 1, 1, 1, 1]);
+        assert_eq!(instructions[1].to_string(), "RST 7");
+
+        assert!(parse_str("RST 8\n").is_err());
+        assert!(parse_str("RST\n").is_err());
+    }
+
+    #[test]
+    fn test_label_on_instruction_line() {
+        let instructions = parse("data/test/label_same_line.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::NoRegister(InstructionCommand::Nop),
+                Instruction::IntermediateRegister(
+                    InstructionCommand::Mvi,
+                    5,
+                    InstructionRegister::A
+                ),
+                Instruction::Label(InstructionCommand::Jmp, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bare_label() {
+        let instructions = parse("data/test/bare_label.asm".to_string()).unwrap();
+
+        assert_eq!(instructions.len(), 5);
+        assert_eq!(
+            instructions[2],
+            Instruction::Label(InstructionCommand::Jmp, 1)
+        );
+        assert_eq!(
+            instructions[3],
+            Instruction::Label(InstructionCommand::Jmp, 3)
+        );
+        // a label after the last instruction points right behind it
+        assert_eq!(
+            instructions[4],
+            Instruction::Label(InstructionCommand::Jmp, 12)
+        );
+    }
+
+    #[test]
+    fn test_label_operands() {
+        let instructions = parse("data/test/label_operands.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions[0],
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Lda, 13)
+        );
+        assert_eq!(
+            instructions[0].encode(),
+            vec![0, 0, 1, 1, 1, 0, 1, 0, 0, 0, 0, 0, 1, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            instructions[1],
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 14)
+        );
+        assert_eq!(
+            instructions[2],
+            Instruction::Intermediate16Bit(
+                InstructionCommand::Lxi,
+                InstructionRegisterPair::HL,
+                14
+            )
+        );
+        assert_eq!(
+            instructions[3],
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Shld, 14)
+        );
+
+        let error = parse("data/test/unknown_label_operand.asm".to_string()).unwrap_err();
+        assert_eq!(error.line, 1);
+        assert_eq!(error.message, "unknown label");
+        assert_eq!(error.text, "NOPE");
+    }
+
+    #[test]
+    fn test_hex_and_decimal_intermediates() {
+        let instructions = parse("data/test/hex_intermediates.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions[0],
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, -1, InstructionRegister::A)
+        );
+        assert_eq!(instructions[0].encode(), instructions[1].encode());
+        assert_eq!(instructions[0].encode(), instructions[2].encode());
+        assert_eq!(instructions[0].encode(), instructions[3].encode());
+
+        assert_eq!(
+            instructions[4],
+            Instruction::Intermediate(InstructionCommand::Adi, 31)
+        );
+        assert_eq!(instructions[4], instructions[5]);
+    }
+
+    #[test]
+    fn test_binary_intermediates() {
+        let instructions =
+            parse_str("MVI B, 00000010\nLXI H, 00000010\nLXI D, 0000000100000001\nMVI C, 10\nHLT")
+                .unwrap();
+
+        // 8 digits of 0 and 1 are binary for 8 and 16 bit operands alike
+        assert_eq!(
+            instructions[0],
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 2, InstructionRegister::B)
+        );
+        assert_eq!(
+            instructions[1],
+            Instruction::Intermediate16Bit(InstructionCommand::Lxi, InstructionRegisterPair::HL, 2)
+        );
+        assert_eq!(
+            instructions[2],
+            Instruction::Intermediate16Bit(
+                InstructionCommand::Lxi,
+                InstructionRegisterPair::DE,
+                0x0101
+            )
+        );
+        // any other number of digits is decimal
+        assert_eq!(
+            instructions[3],
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 10, InstructionRegister::C)
+        );
+    }
+
+    #[test]
+    fn test_intermediate_out_of_range() {
+        let error = parse("data/test/intermediate_out_of_range.asm".to_string()).unwrap_err();
+
+        assert_eq!(error.line, 1);
+        assert_eq!(error.message, "intermediate out of range");
+        assert_eq!(error.text, "256");
+    }
+
+    #[test]
+    fn test_signed_intermediates() {
+        let instructions = parse("data/test/signed_intermediates.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions[0].encode(),
+            vec![1, 1, 0, 0, 0, 1, 1, 0, 1, 1, 1, 1, 1, 0, 1, 1]
+        );
+        assert_eq!(
+            instructions[1].encode(),
+            vec![1, 1, 0, 1, 0, 1, 1, 0, 1, 1, 0, 0, 1, 0, 0, 0]
+        );
+        assert_eq!(
+            instructions[2],
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, -1, InstructionRegister::C)
+        );
+        assert_eq!(
+            instructions[3],
+            Instruction::Intermediate16Bit(
+                InstructionCommand::Lxi,
+                InstructionRegisterPair::BC,
+                -32768
+            )
+        );
+        assert_eq!(
+            instructions[4],
+            Instruction::Intermediate16Bit(
+                InstructionCommand::Lxi,
+                InstructionRegisterPair::DE,
+                -1
+            )
+        );
+        assert_eq!(
+            instructions[5],
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 0x1234)
+        );
+    }
+
+    #[test]
+    fn test_signed_and_unsigned_intermediates() {
+        let instructions = parse_str("MVI A, 200\nADI 200\nADI -56\nHLT").unwrap();
+
+        assert_eq!(
+            instructions[0],
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, -56, InstructionRegister::A)
+        );
+        assert_eq!(instructions[1], instructions[2]);
+        assert_eq!(instructions[1].to_string(), "ADI 0xC8");
+
+        for source in ["ADI 256", "ADI -129", "MVI A, 300"] {
+            let error = parse_str(source).unwrap_err();
+            assert_eq!(error.message, "intermediate out of range");
+        }
+    }
+
+    #[test]
+    fn test_intermediate_16_bit_out_of_range() {
+        let error =
+            parse("data/test/intermediate_16_bit_out_of_range.asm".to_string()).unwrap_err();
+
+        assert_eq!(error.message, "intermediate out of range");
+        assert_eq!(error.text, "65536");
+    }
+
+    #[test]
+    fn test_define_byte() {
+        let instructions = parse("data/test/define_byte.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions[1],
+            Instruction::Data(InstructionCommand::Db, vec![1, 2, -1])
+        );
+        assert_eq!(
+            instructions[1].encode(),
+            vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1]
+        );
+        assert_eq!(
+            instructions[2],
+            Instruction::Data(InstructionCommand::Db, vec![72, 73, 0])
+        );
+
+        // JMP (3 bytes) + TABLE (3 bytes) + TEXT (3 bytes)
+        assert_eq!(
+            instructions[0],
+            Instruction::Label(InstructionCommand::Jmp, 9)
+        );
+    }
+
+    #[test]
+    fn test_define_word() {
+        let instructions = parse("data/test/define_word.asm".to_string()).unwrap();
+
+        // NEXT is at MVI (2 bytes) + WORDS (4 bytes)
+        assert_eq!(
+            instructions[1],
+            Instruction::Data(InstructionCommand::Dw, vec![0x34, 0x12, 6, 0])
+        );
+        assert_eq!(
+            instructions[2],
+            Instruction::Data(InstructionCommand::Dw, vec![0, 0, -1, -1])
+        );
+        assert_eq!(instructions[1].get_size(), 4);
+    }
+
+    #[test]
+    fn test_origin() {
+        let instructions = parse("data/test/origin.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions[0],
+            Instruction::Origin(InstructionCommand::Org, 0x0100)
+        );
+        assert_eq!(
+            instructions[2],
+            Instruction::Label(InstructionCommand::Jmp, 0x0100)
+        );
+    }
+
+    #[test]
+    fn test_origin_wraps_around() {
+        let instructions = parse_str("ORG 0xFFFF\nLXI H,0\nLOOP: NOP\nJMP LOOP\n").unwrap();
+
+        assert_eq!(
+            instructions[3],
+            Instruction::Label(InstructionCommand::Jmp, 0x0002)
+        );
+    }
+
+    #[test]
+    fn test_unknown_label() {
+        let error = parse("data/test/unknown_label.asm".to_string()).unwrap_err();
+
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "unknown label");
+        assert_eq!(error.text, "NOPE");
+    }
+
+    #[test]
+    fn test_unknown_mnemonic() {
+        let error = parse("data/test/unknown_mnemonic.asm".to_string()).unwrap_err();
+
+        assert_eq!(error.line, 3);
+        assert_eq!(error.column, 9);
+        assert_eq!(error.message, "unknown mnemonic");
+        assert_eq!(error.text, "FOO");
+        assert_eq!(
+            error.to_string(),
+            "line 3, column 9: unknown mnemonic: \"FOO\""
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let lowercase = parse("data/test/lowercase.asm".to_string()).unwrap();
+        let uppercase = parse("data/test/uppercase.asm".to_string()).unwrap();
+
+        assert_eq!(lowercase, uppercase);
+    }
+
+    #[test]
+    fn test_display_register_pair() {
+        assert_eq!(InstructionRegisterPair::BC.to_string(), "B");
+        assert_eq!(InstructionRegisterPair::DE.to_string(), "D");
+        assert_eq!(InstructionRegisterPair::HL.to_string(), "H");
+        assert_eq!(InstructionRegisterPair::SP.to_string(), "SP");
+        assert_eq!(InstructionRegisterPair::FA.to_string(), "PSW");
+    }
+
+    #[test]
+    fn test_parse_register_pairs() {
+        assert_eq!(
+            parse_str("PUSH PSW\npop psw\nLXI SP,0\nINX d\nDAD H\nHLT").unwrap(),
+            vec![
+                Instruction::PairRegister(InstructionCommand::Push, InstructionRegisterPair::FA),
+                Instruction::PairRegister(InstructionCommand::Pop, InstructionRegisterPair::FA),
+                Instruction::Intermediate16Bit(
+                    InstructionCommand::Lxi,
+                    InstructionRegisterPair::SP,
+                    0
+                ),
+                Instruction::PairRegister(InstructionCommand::Inx, InstructionRegisterPair::DE),
+                Instruction::PairRegister(InstructionCommand::Dad, InstructionRegisterPair::HL),
+                Instruction::NoRegister(InstructionCommand::Hlt),
+            ]
+        );
+    }
+
+    // masks read better unsigned, however the immediate was written
+    #[test]
+    fn test_display_logical_immediates() {
+        let instructions = parse_str("ANI -1\nORI -16\nXRI 0x80\nHLT").unwrap();
+        assert_eq!(instructions[0].to_string(), "ANI 0xFF");
+        assert_eq!(instructions[1].to_string(), "ORI 0xF0");
+        assert_eq!(instructions[2].to_string(), "XRI 0x80");
+
+        let (decoded, _) = Instruction::decode_one(&[0xe6, 0xff]).unwrap();
+        assert_eq!(decoded.to_string(), "ANI 0xFF");
+    }
+
+    #[test]
+    fn test_semantic_eq() {
+        let assembled = &parse_str("ANI 0xFF\nJMP $\nORG 0x0100\nHLT").unwrap();
+
+        let (decoded, _) = Instruction::decode_one(&[0xe6, 0xff]).unwrap();
+        assert!(assembled[0].semantic_eq(&decoded));
+        assert!(assembled[0].semantic_eq(&Instruction::Intermediate(InstructionCommand::Ani, -1)));
+        assert!(!assembled[0].semantic_eq(&Instruction::Intermediate(InstructionCommand::Ani, 1)));
+
+        let (decoded, _) = Instruction::decode_one(&[0xc3, 0x02, 0x00]).unwrap();
+        assert!(assembled[1].semantic_eq(&decoded));
+
+        assert!(assembled[2].semantic_eq(&Instruction::Origin(InstructionCommand::Org, 0x0100)));
+        assert!(!assembled[2].semantic_eq(&Instruction::Origin(InstructionCommand::Org, 0)));
+        assert!(!assembled[3].semantic_eq(&Instruction::Data(InstructionCommand::Db, vec![0x76])));
+    }
+
+    #[test]
+    fn test_display_instruction() {
+        assert_eq!(
+            Instruction::SingleRegister(InstructionCommand::Add, InstructionRegister::A)
+                .to_string(),
+            "ADD A"
+        );
+        assert_eq!(
+            Instruction::DoubleRegister(
+                InstructionCommand::Mov,
+                (InstructionRegister::B, InstructionRegister::M)
+            )
+            .to_string(),
+            "MOV B, M"
+        );
+        assert_eq!(
+            Instruction::IntermediateRegister(
+                InstructionCommand::Mvi,
+                0x1F,
+                InstructionRegister::C
+            )
+            .to_string(),
+            "MVI C, 0x1F"
+        );
+        assert_eq!(
+            Instruction::Intermediate(InstructionCommand::Sui, -1).to_string(),
+            "SUI 0xFF"
+        );
+        assert_eq!(
+            Instruction::Intermediate16Bit(
+                InstructionCommand::Lxi,
+                InstructionRegisterPair::HL,
+                0x1234
+            )
+            .to_string(),
+            "LXI H, 0x1234"
+        );
+        assert_eq!(
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 42).to_string(),
+            "STA 0x002A"
+        );
+        assert_eq!(
+            Instruction::Label(InstructionCommand::Jmp, 0x004B).to_string(),
+            "JMP 0x004B"
+        );
+        assert_eq!(
+            Instruction::PairRegister(InstructionCommand::Push, InstructionRegisterPair::FA)
+                .to_string(),
+            "PUSH PSW"
+        );
+        assert_eq!(
+            Instruction::NoRegister(InstructionCommand::Hlt).to_string(),
+            "HLT"
+        );
+        assert_eq!(
+            Instruction::Data(InstructionCommand::Db, vec![1, 'A' as i8]).to_string(),
+            "DB 0x01, 0x41"
+        );
+        assert_eq!(
+            Instruction::Data(InstructionCommand::Dw, vec![0x34, 0x12]).to_string(),
+            "DW 0x1234"
+        );
+    }
+
+    #[test]
+    fn test_io_instructions() {
+        let instructions = parse("data/test/io.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions,
+            vec![
+                Instruction::Intermediate(InstructionCommand::In, 1),
+                Instruction::SingleRegister(InstructionCommand::Inr, InstructionRegister::A),
+                Instruction::Intermediate(InstructionCommand::Out, 0x10),
+                Instruction::PairRegister(InstructionCommand::Inx, InstructionRegisterPair::BC),
+            ]
+        );
+        assert_eq!(
+            instructions[2].encode(),
+            vec![1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_current_address() {
+        let instructions = parse("data/test/current_address.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions[1],
+            Instruction::Label(InstructionCommand::Jmp, 2)
+        );
+        assert_eq!(
+            instructions[2],
+            Instruction::Intermediate16Bit(InstructionCommand::Lxi, InstructionRegisterPair::HL, 5)
+        );
+        assert_eq!(
+            instructions[3],
+            Instruction::Data(InstructionCommand::Dw, vec![8, 0])
+        );
+        assert_eq!(
+            instructions[5],
+            Instruction::IntermediateRegister(
+                InstructionCommand::Mvi,
+                0x10,
+                InstructionRegister::B
+            )
+        );
+        assert_eq!(
+            instructions[6],
+            Instruction::Label(InstructionCommand::Jnz, 2)
+        );
+    }
+
+    #[test]
+    fn test_label_offset() {
+        let instructions = parse("data/test/label_offset.asm".to_string()).unwrap();
+
+        assert_eq!(
+            instructions[1],
+            Instruction::Intermediate16Bit(InstructionCommand::Lxi, InstructionRegisterPair::HL, 2)
+        );
+        assert_eq!(
+            instructions[2],
+            Instruction::Intermediate16Bit(
+                InstructionCommand::Lxi,
+                InstructionRegisterPair::DE,
+                0x10
+            )
+        );
+        assert_eq!(
+            instructions[3],
+            Instruction::Label(InstructionCommand::Jmp, 7)
+        );
+        assert_eq!(
+            instructions[4],
+            Instruction::Data(InstructionCommand::Dw, vec![14, 0])
+        );
+
+        let error = parse("data/test/label_offset_range.asm".to_string()).unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "address out of range");
+        assert_eq!(error.text, "LABEL-1000");
+    }
+
+    #[test]
+    fn test_invalid_register_pairs() {
+        let error = parse("data/test/stax_h.asm".to_string()).unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "invalid register pair for STAX");
+        assert_eq!(error.text, "H");
+
+        let error = parse("data/test/push_sp.asm".to_string()).unwrap_err();
+        assert_eq!(error.line, 1);
+        assert_eq!(error.message, "invalid register pair for PUSH");
+        assert_eq!(error.text, "SP");
+        assert_eq!(
+            error.to_string(),
+            "line 1, column 14: invalid register pair for PUSH: \"SP\""
+        );
+    }
+
+    #[test]
+    fn test_register_alias() {
+        let aliased = parse("data/test/register_alias.asm".to_string()).unwrap();
+        let literal = parse("data/test/register_alias_literal.asm".to_string()).unwrap();
+
+        assert_eq!(aliased, literal);
+    }
+
+    #[test]
+    fn test_invalid_register_alias() {
+        let error = parse_str("B EQU C\nHLT").unwrap_err();
+        assert_eq!(error.message, "register alias can't occupy reserved names");
+        assert_eq!(error.text, "B");
+
+        let error = parse_str("CNT EQU C\nCNT EQU D\nHLT").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "duplicate register alias");
+
+        let error = parse_str("LOOP EQU C\nLOOP: HLT").unwrap_err();
+        assert_eq!(error.message, "register alias collides with a label");
+        assert_eq!(error.text, "LOOP");
+
+        let error = parse_str("INR COUNTER\nHLT").unwrap_err();
+        assert_eq!(error.message, "unknown register alias");
+        assert_eq!(error.text, "COUNTER");
+        assert_eq!(error.column, 5);
+    }
+
+    #[test]
+    fn test_computed_call() {
+        let error = parse_str("        LXI H,0x0100\n        CALL H\n        HLT").unwrap_err();
+
+        assert_eq!(error.line, 2);
+        assert_eq!(error.column, 9);
+        assert_eq!(
+            error.message,
+            "CALL can't jump to a register pair, use PCHL"
+        );
+        assert_eq!(error.text, "CALL H");
+
+        // labels starting with the name of a pair are called like any other
+        assert_eq!(
+            parse_str("BDOS:   RET\n        CALL BDOS").unwrap()[1],
+            Instruction::Label(InstructionCommand::Call, 0)
+        );
     }
 }
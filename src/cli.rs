@@ -0,0 +1,189 @@
+use crate::assembler::{Assembler, AssemblerError};
+use crate::cpu;
+use std::io::{BufRead, Write};
+
+pub const USAGE: &str = "usage: emu8080 <input.asm> [output] [--print | --step]";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    // only the final status is printed
+    Quiet,
+    // the status is printed after every instruction
+    Print,
+    // like print, but waits for a line on the input before every instruction
+    Step,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub input: String,
+    pub output: String,
+    pub mode: Mode,
+}
+
+// args without the program name, the output defaults to "output"
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut paths = Vec::new();
+    let mut mode = Mode::Quiet;
+
+    for arg in args {
+        let flag = match arg.as_str() {
+            "--print" => Mode::Print,
+            "--step" => Mode::Step,
+            _ if arg.starts_with("--") => return Err(format!("unknown flag {}", arg)),
+            _ => {
+                paths.push(arg.clone());
+                continue;
+            }
+        };
+
+        if mode != Mode::Quiet && mode != flag {
+            return Err("--print and --step can't be combined".to_owned());
+        }
+        mode = flag;
+    }
+
+    let mut paths = paths.into_iter();
+    let input = paths
+        .next()
+        .ok_or_else(|| "missing input file".to_owned())?;
+    let output = paths.next().unwrap_or_else(|| "output".to_owned());
+    if let Some(extra) = paths.next() {
+        return Err(format!("unexpected argument {}", extra));
+    }
+
+    Ok(Options {
+        input,
+        output,
+        mode,
+    })
+}
+
+// assembles the input into the output file and runs it until HLT
+pub fn run(
+    options: &Options,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> Result<(), AssemblerError> {
+    let instructions =
+        Assembler::new(options.input.clone(), options.output.clone()).assemble_indexed()?;
+
+    let mut cpu = cpu::initialize_cpu();
+    match options.mode {
+        Mode::Quiet => {
+            cpu.try_run_program(&instructions)?;
+            cpu.print_status(out)?;
+        }
+        Mode::Print => cpu.print_run(&instructions, out)?,
+        Mode::Step => {
+            writeln!(out, "Initial status:")?;
+            cpu.print_status(out)?;
+            cpu.load_program(instructions.clone());
+
+            let mut line = String::new();
+            while !cpu.is_halted() {
+                // stops early once the input is closed
+                line.clear();
+                if input.read_line(&mut line)? == 0 {
+                    break;
+                }
+
+                let address = cpu.program_counter() as usize;
+                writeln!(out, "-------------")?;
+                if let Some(Some(instruction)) = instructions.get(address) {
+                    writeln!(out, "{}", instruction)?;
+                }
+                cpu.step_with_changes()?;
+                cpu.print_status(out)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse_args(&args(&["test.asm"])),
+            Ok(Options {
+                input: "test.asm".to_owned(),
+                output: "output".to_owned(),
+                mode: Mode::Quiet,
+            })
+        );
+        assert_eq!(
+            parse_args(&args(&["--step", "test.asm", "test.bin"])),
+            Ok(Options {
+                input: "test.asm".to_owned(),
+                output: "test.bin".to_owned(),
+                mode: Mode::Step,
+            })
+        );
+
+        assert!(parse_args(&args(&[])).is_err());
+        assert!(parse_args(&args(&["--print", "--step", "test.asm"])).is_err());
+        assert!(parse_args(&args(&["--fast", "test.asm"])).is_err());
+        assert!(parse_args(&args(&["a.asm", "b.bin", "c"])).is_err());
+    }
+
+    #[test]
+    fn test_run() {
+        let output = "test_cli_run_output";
+        let mut options = parse_args(&args(&["data/test/end_to_end.asm", output])).unwrap();
+
+        let mut quiet = Vec::new();
+        run(&options, &mut Cursor::new(""), &mut quiet).unwrap();
+        assert!(fs::metadata(output).is_ok());
+
+        options.mode = Mode::Print;
+        let mut printed = Vec::new();
+        run(&options, &mut Cursor::new(""), &mut printed).unwrap();
+        fs::remove_file(output).unwrap();
+
+        // both end in the same final status
+        let quiet = String::from_utf8(quiet).unwrap();
+        let printed = String::from_utf8(printed).unwrap();
+        assert!(printed.starts_with("Initial status:"));
+        assert!(printed.ends_with(&quiet));
+    }
+
+    #[test]
+    fn test_run_without_halt() {
+        let source = "test_cli_without_halt.asm";
+        let output = "test_cli_without_halt_output";
+        fs::write(source, "MVI A,1\n").unwrap();
+        let options = parse_args(&args(&[source, output])).unwrap();
+
+        let error = run(&options, &mut Cursor::new(""), &mut Vec::new()).unwrap_err();
+        fs::remove_file(source).unwrap();
+        fs::remove_file(output).unwrap();
+        assert!(matches!(error, AssemblerError::InvalidInstruction(2, 0)));
+    }
+
+    #[test]
+    fn test_run_step() {
+        let output = "test_cli_step_output";
+        let source = "data/test/end_to_end.asm";
+        let options = parse_args(&args(&["--step", source, output])).unwrap();
+
+        // two steps, then the input runs out
+        let mut out = Vec::new();
+        run(&options, &mut Cursor::new("\n\n"), &mut out).unwrap();
+        fs::remove_file(output).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("-------------").count(), 2);
+        assert!(!out.contains("Execution finished"));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::assembler::InstructionRegister;
+use crate::cpu::{initialize_cpu, Cpu};
+
+// content of memory before the program writes to it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FillPattern {
+    Zero,
+    // the same seed always gives the same memory
+    Seed(u64),
+}
+
+#[derive(Debug)]
+pub struct CpuBuilder {
+    memory_size: usize,
+    fill_pattern: FillPattern,
+    program_counter: u16,
+    stack_pointer: u16,
+    registers: Vec<(InstructionRegister, i8)>,
+}
+
+impl Default for CpuBuilder {
+    fn default() -> Self {
+        CpuBuilder::new()
+    }
+}
+
+impl CpuBuilder {
+    pub fn new() -> CpuBuilder {
+        CpuBuilder {
+            memory_size: 65536,
+            fill_pattern: FillPattern::Zero,
+            program_counter: 0,
+            stack_pointer: 0,
+            registers: Vec::new(),
+        }
+    }
+
+    // addresses beyond the memory size wrap around
+    pub fn memory_size(mut self, memory_size: usize) -> CpuBuilder {
+        if memory_size == 0 || memory_size > 65536 {
+            panic!("invalid memory size: {}", memory_size);
+        }
+
+        self.memory_size = memory_size;
+        self
+    }
+
+    // random memory makes programs that expect zeroed memory fail
+    pub fn fill_memory(mut self, fill_pattern: FillPattern) -> CpuBuilder {
+        self.fill_pattern = fill_pattern;
+        self
+    }
+
+    pub fn program_counter(mut self, program_counter: u16) -> CpuBuilder {
+        self.program_counter = program_counter;
+        self
+    }
+
+    pub fn stack_pointer(mut self, stack_pointer: u16) -> CpuBuilder {
+        self.stack_pointer = stack_pointer;
+        self
+    }
+
+    pub fn register(mut self, register: InstructionRegister, value: i8) -> CpuBuilder {
+        self.registers.push((register, value));
+        self
+    }
+
+    pub fn build(self) -> Cpu {
+        let mut cpu = initialize_cpu();
+
+        cpu.memory = match self.fill_pattern {
+            FillPattern::Zero => vec![0; self.memory_size],
+            FillPattern::Seed(seed) => random_memory(seed, self.memory_size),
+        };
+        cpu.program_counter = self.program_counter;
+        cpu.stack_pointer = self.stack_pointer;
+        for (register, value) in self.registers {
+            cpu.change_register(register, value);
+        }
+
+        cpu
+    }
+}
+
+// splitmix64, good enough to spot reads of uninitialized memory and works with any seed
+fn random_memory(seed: u64, size: usize) -> Vec<i8> {
+    let mut state = seed;
+    let mut memory = Vec::with_capacity(size);
+
+    while memory.len() < size {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^= value >> 31;
+
+        for byte in value.to_le_bytes() {
+            if memory.len() < size {
+                memory.push(byte as i8);
+            }
+        }
+    }
+
+    memory
+}
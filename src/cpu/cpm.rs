@@ -0,0 +1,147 @@
+use crate::assembler::{AssemblerError, Instruction};
+use crate::cpu::{initialize_cpu, Cpu};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// CP/M loads programs at 0x0100, they print by calling the BDOS at 0x0005
+// and return to CP/M by jumping to 0x0000
+const PROGRAM_START: u16 = 0x0100;
+const BDOS: u16 = 0x0005;
+const WARM_BOOT: u16 = 0x0000;
+
+// runs a CP/M .COM program until it jumps back to CP/M or halts, returns everything it printed
+// with BDOS functions 2 (character in E) and 9 (string at DE up to $), e.g. TST8080.COM
+pub fn run_com(image: &[u8]) -> Result<String, AssemblerError> {
+    let mut cpu = initialize_cpu();
+    for (offset, byte) in image.iter().enumerate() {
+        cpu.set_memory(PROGRAM_START.wrapping_add(offset as u16), *byte as i8);
+    }
+    cpu.set_program_counter(PROGRAM_START);
+    // programs that don't set up a stack still need one for their calls
+    cpu.set_stack_pointer(0xff00);
+
+    let output = Rc::new(RefCell::new(String::new()));
+    let captured = output.clone();
+    cpu.set_syscall_handler(
+        BDOS,
+        Box::new(move |function, address, memory| match function {
+            2 => captured.borrow_mut().push(address as u8 as char),
+            9 => captured.borrow_mut().extend(
+                memory[address as usize..]
+                    .iter()
+                    .take_while(|byte| **byte as u8 != b'$')
+                    .map(|byte| *byte as u8 as char),
+            ),
+            _ => (),
+        }),
+    );
+
+    cpu.load_program(decode_memory(&cpu));
+    // code the emulator can't decode, e.g. the undocumented 0xDD, ends it with an error
+    while cpu.get_program_counter() != WARM_BOOT && cpu.step()? {}
+
+    let output = output.borrow().clone();
+    Ok(output)
+}
+
+// every address is decoded on its own, so jumps into the middle of data still find their code
+fn decode_memory(cpu: &Cpu) -> Vec<Option<Instruction>> {
+    let bytes: Vec<u8> = cpu.memory.iter().map(|byte| *byte as u8).collect();
+
+    (0..bytes.len())
+        .map(|address| {
+            Instruction::decode_one(&bytes[address..])
+                .ok()
+                .map(|(instruction, _)| instruction)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+
+    #[test]
+    fn test_run_com() {
+        let source = "BOOT:   NOP
+        ORG 5
+BDOS:   NOP
+        ORG 0x0100
+        LXI SP,0x1000
+        MVI C,9
+        LXI D,MSG
+        CALL BDOS
+        MVI C,2
+        MVI E,33 ; !
+        CALL BDOS
+        JMP BOOT
+MSG:    DB 'CPU IS OPERATIONAL$'
+";
+        // the image starts at 0x0100, BOOT and BDOS only give the jumps their targets
+        let bytes = assembler::assemble_str(source).unwrap();
+        let output = run_com(&bytes[0x0100..]).unwrap();
+
+        assert_eq!(output, "CPU IS OPERATIONAL!");
+    }
+
+    #[test]
+    fn test_run_com_self_check() {
+        // checks the way TST8080 does, conditional calls into an error routine
+        let source = "BOOT:   NOP
+        ORG 5
+BDOS:   NOP
+        ORG 0x0100
+        LXI SP,0x1000
+        MVI A,0x15
+        ADI 0x27
+        DAA
+        CPI 0x42
+        CNZ FAIL
+        MVI B,0x0F
+        XRA A
+        CNZ FAIL
+        CC FAIL
+        MOV A,B
+        CPI 0x0F
+        CNZ FAIL
+        MVI C,9
+        LXI D,OK
+        CALL BDOS
+        JMP BOOT
+FAIL:   MVI C,9
+        LXI D,ERR
+        CALL BDOS
+        JMP BOOT
+OK:     DB 'CPU IS OPERATIONAL$'
+ERR:    DB 'CPU HAS FAILED$'
+";
+        let bytes = assembler::assemble_str(source).unwrap();
+        let output = run_com(&bytes[0x0100..]).unwrap();
+
+        assert_eq!(output, "CPU IS OPERATIONAL");
+    }
+
+    #[test]
+    fn test_run_com_unknown_instruction() {
+        // undocumented copy of CALL 0x0005
+        let error = run_com(&[0xdd, 0x05, 0x00]).unwrap_err();
+
+        assert!(matches!(
+            error,
+            AssemblerError::InvalidInstruction(0x0100, 0xdd)
+        ));
+    }
+
+    // the exerciser isn't part of the repository, get TST8080.COM and run
+    // EMU8080_EXERCISER=path/to/TST8080.COM cargo test -- --ignored
+    #[test]
+    #[ignore]
+    fn test_exerciser() {
+        let path = std::env::var("EMU8080_EXERCISER").expect("EMU8080_EXERCISER isn't set");
+        let image = std::fs::read(path).unwrap();
+
+        let output = run_com(&image).unwrap();
+        assert!(output.contains("CPU IS OPERATIONAL"), "{}", output);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::assembler::{Instruction, InstructionCommand, InstructionRegister};
+
+// number of t-states the 8080 needs for an instruction, conditional jumps take the same time
+// whether they're taken or not, conditional calls and returns are counted as not taken,
+// directives don't take any
+pub fn t_states(instruction: &Instruction) -> u64 {
+    match instruction {
+        Instruction::DoubleRegister(_, (InstructionRegister::M, _))
+        | Instruction::DoubleRegister(_, (_, InstructionRegister::M)) => 7,
+        Instruction::DoubleRegister(_, _) => 5,
+        Instruction::SingleRegister(command, register) => {
+            let memory = *register == InstructionRegister::M;
+            match command {
+                InstructionCommand::Inr | InstructionCommand::Dcr if memory => 10,
+                InstructionCommand::Inr | InstructionCommand::Dcr => 5,
+                _ if memory => 7,
+                _ => 4,
+            }
+        }
+        Instruction::IntermediateRegister(_, _, InstructionRegister::M) => 10,
+        Instruction::IntermediateRegister(_, _, _) => 7,
+        Instruction::Intermediate(command, _) => match command {
+            InstructionCommand::In | InstructionCommand::Out => 10,
+            _ => 7,
+        },
+        Instruction::Intermediate16Bit(_, _, _) => 10,
+        Instruction::Intermediate16BitNoReg(command, _) => match command {
+            InstructionCommand::Shld | InstructionCommand::Lhld => 16,
+            _ => 13,
+        },
+        Instruction::PairRegister(command, _) => match command {
+            InstructionCommand::Stax | InstructionCommand::Ldax => 7,
+            InstructionCommand::Push => 11,
+            InstructionCommand::Pop | InstructionCommand::Dad => 10,
+            _ => 5,
+        },
+        Instruction::Label(command, _) => match command {
+            InstructionCommand::Call => 17,
+            InstructionCommand::Cc
+            | InstructionCommand::Cnc
+            | InstructionCommand::Cz
+            | InstructionCommand::Cnz
+            | InstructionCommand::Cm
+            | InstructionCommand::Cp
+            | InstructionCommand::Cpe
+            | InstructionCommand::Cpo => 11,
+            _ => 10,
+        },
+        Instruction::Restart(_, _) => 11,
+        Instruction::NoRegister(command) => match command {
+            InstructionCommand::Hlt => 7,
+            InstructionCommand::Ret => 10,
+            InstructionCommand::Rc
+            | InstructionCommand::Rnc
+            | InstructionCommand::Rz
+            | InstructionCommand::Rnz
+            | InstructionCommand::Rm
+            | InstructionCommand::Rp
+            | InstructionCommand::Rpe
+            | InstructionCommand::Rpo => 5,
+            InstructionCommand::Xthl => 18,
+            InstructionCommand::Sphl | InstructionCommand::Pchl => 5,
+            _ => 4,
+        },
+        Instruction::Data(_, _) | Instruction::Origin(_, _) => 0,
+    }
+}
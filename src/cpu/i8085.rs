@@ -0,0 +1,8 @@
+// minimal model of the interrupt masks and serial lines of the 8085
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct I8085State {
+    // masks of RST 5.5, RST 6.5 and RST 7.5 in bit 0 to 2
+    pub interrupt_masks: u8,
+    pub serial_input: bool,
+    pub serial_output: bool,
+}
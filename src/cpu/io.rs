@@ -0,0 +1,19 @@
+use std::fmt;
+
+// devices are reached through the ports of IN and OUT
+pub trait IoBus: fmt::Debug {
+    fn read(&mut self, port: u8) -> u8;
+    fn write(&mut self, port: u8, value: u8);
+}
+
+// bus without any devices attached, reads return 0 and writes are dropped
+#[derive(Debug)]
+pub struct NoopBus;
+
+impl IoBus for NoopBus {
+    fn read(&mut self, _port: u8) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _port: u8, _value: u8) {}
+}
@@ -1,10 +1,53 @@
 use crate::assembler::{
-    Instruction, InstructionCommand, InstructionRegister, InstructionRegisterPair,
+    self, AssemblerError, Instruction, InstructionCommand, InstructionRegister,
+    InstructionRegisterPair,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
+mod builder;
+mod cpm;
+mod cycles;
+#[cfg(feature = "i8085")]
+mod i8085;
+mod io;
+mod stack;
+mod state;
+mod syscall;
+mod trace;
+
+pub use builder::{CpuBuilder, FillPattern};
+pub use cpm::run_com;
+pub use io::{IoBus, NoopBus};
+use stack::StackGuard;
+pub use state::CpuState;
+use syscall::{SyscallFn, SyscallHandler};
+pub use trace::TraceRecord;
+use trace::Tracer;
+
+// assembles source into memory at 0 and runs it from the first instruction, e.g. the one
+// behind ORG 0x0100, until HLT
+pub fn run_source(source: &str) -> Result<(Cpu, RunSummary), AssemblerError> {
+    let (bytes, instructions) = assembler::assemble_str_with_index(source)?;
+
+    let mut cpu = initialize_cpu();
+    for (address, byte) in bytes.iter().enumerate() {
+        cpu.set_memory(address as u16, *byte as i8);
+    }
+    let start = instructions.iter().position(Option::is_some).unwrap_or(0);
+    cpu.set_program_counter(start as u16);
+    let summary = cpu.try_run_program(&instructions)?;
+
+    Ok((cpu, summary))
+}
+
+// registers hold signed values, the low byte must not spill into the high one
+fn pair_to_address(high: i8, low: i8) -> u16 {
+    ((high as u8 as u16) << 8) | low as u8 as u16
+}
+
 pub fn initialize_cpu() -> Cpu {
     Cpu {
         registers: vec![0; 8],
@@ -12,6 +55,23 @@ pub fn initialize_cpu() -> Cpu {
         stack_pointer: 0,
         flags: vec![false; 8],
         program_counter: 0,
+        io_bus: Box::new(NoopBus),
+        interrupts_enabled: false,
+        pending_interrupt: None,
+        program: Vec::new(),
+        breakpoints: HashSet::new(),
+        watchpoints: HashSet::new(),
+        watchpoint_hit: None,
+        memory_changes: None,
+        detect_deadlock: false,
+        stop_on_halt: true,
+        tracer: None,
+        stack_guard: None,
+        syscall_handlers: HashMap::new(),
+        rom_regions: Vec::new(),
+        halted: false,
+        #[cfg(feature = "i8085")]
+        i8085: i8085::I8085State::default(),
     }
 }
 
@@ -24,7 +84,107 @@ pub struct Cpu {
     // S Z x A x P x C
     flags: Vec<bool>,
     program_counter: u16,
+    io_bus: Box<dyn IoBus>,
+    interrupts_enabled: bool,
+    // RST vector of the interrupt waiting to be handled
+    pending_interrupt: Option<u8>,
+
+    // instructions indexed by their address, used by run_until_break
+    program: Vec<Option<Instruction>>,
+    breakpoints: HashSet<u16>,
+    watchpoints: HashSet<u16>,
+    // address and new value of the last write to a watched address
+    watchpoint_hit: Option<(u16, i8)>,
+    // cells written by the current step_with_changes, only recorded while it runs
+    memory_changes: Option<Vec<(u16, i8)>>,
+    // makes run_until_break stop at jumps to themselves instead of spinning forever
+    detect_deadlock: bool,
+    // without it HLT only stops at the end of the program, see set_stop_on_halt
+    stop_on_halt: bool,
+    tracer: Option<Tracer>,
+    // disabled by default, the 8080 itself doesn't care where the stack goes
+    stack_guard: Option<StackGuard>,
+    // addresses whose code is replaced by a handler, e.g. the CP/M BDOS entry at 0x0005
+    syscall_handlers: HashMap<u16, SyscallHandler>,
+    // start..=end of every write protected region, memory is all RAM by default
+    rom_regions: Vec<(u16, u16)>,
+    // set by HLT, cleared when execution starts again
+    halted: bool,
+    #[cfg(feature = "i8085")]
+    i8085: i8085::I8085State,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BreakReason {
+    // address of the breakpoint
+    Breakpoint(u16),
+    // watched address and the value written to it
+    Watchpoint(u16, i8),
+    Halt,
+    // address of a jump to itself, only reported with deadlock detection enabled
+    Deadlock(u16),
+    // address without a loaded instruction, e.g. bytes that don't decode
+    NoInstruction(u16),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RunOutcome {
+    // RET got back to the return address pushed by call_subroutine
+    Returned,
+    // address of the HLT reached before the subroutine returned
+    Halted(u16),
+    // address without a loaded instruction, like BreakReason::NoInstruction
+    NoInstruction(u16),
+}
+
+// return address of call_subroutine, never executed
+const SUBROUTINE_SENTINEL: u16 = 0xffff;
+
+#[derive(Debug, PartialEq)]
+pub struct RunSummary {
+    // number of executed instructions, including the final HLT
+    pub instructions: u64,
+    // address of the HLT that stopped execution
+    pub halt_address: u16,
+}
+
+// the five flags the 8080 has, without the unused bits of the flags register
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flags {
+    pub sign: bool,
+    pub zero: bool,
+    pub aux_carry: bool,
+    pub parity: bool,
+    pub carry: bool,
+}
+
+// number format of print_status_radix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Radix {
+    Binary,
+    Decimal,
+    Hex,
+}
+
+impl Radix {
+    // values are padded to the given number of bits, e.g. 0x0042 for 16
+    fn format(&self, value: u16, bits: usize) -> String {
+        match self {
+            Radix::Binary => format!("{:#0width$b}", value, width = bits + 2),
+            Radix::Decimal => value.to_string(),
+            Radix::Hex => format!("{:#0width$x}", value, width = bits / 4 + 2),
+        }
+    }
 }
+
+// tells the fetch loop if an instruction already moved the program counter
+#[derive(Debug, PartialEq)]
+enum ProgramFlow {
+    Next,
+    Jump,
+}
+
 #[derive(Debug, EnumIter, Clone)]
 enum Flag {
     S,
@@ -47,30 +207,441 @@ impl Flag {
 }
 
 impl Cpu {
+    // M isn't a real register, it's the memory cell HL points to
     fn get_register(&self, register: InstructionRegister) -> i8 {
+        if let InstructionRegister::M = register {
+            return self.get_memory(self.hl_address());
+        }
+
         self.registers[register.to_index() as usize]
     }
 
     fn change_register(&mut self, register: InstructionRegister, value: i8) {
+        if let InstructionRegister::M = register {
+            self.set_memory(self.hl_address(), value);
+            return;
+        }
+
         self.registers[register.to_index() as usize] = value;
     }
 
+    fn hl_address(&self) -> u16 {
+        pair_to_address(
+            self.get_register(InstructionRegister::H),
+            self.get_register(InstructionRegister::L),
+        )
+    }
+
+    // PSW is A in the high byte and the flags in the low one
+    pub fn register_pair(&self, pair: InstructionRegisterPair) -> u16 {
+        match pair {
+            InstructionRegisterPair::SP => self.get_stack_pointer(),
+            InstructionRegisterPair::FA => pair_to_address(
+                self.get_register(InstructionRegister::A),
+                self.flags_byte() as i8,
+            ),
+            _ => {
+                let registers = pair.get_registers();
+                pair_to_address(
+                    self.get_register(registers.0),
+                    self.get_register(registers.1),
+                )
+            }
+        }
+    }
+
+    pub fn set_register_pair(&mut self, pair: InstructionRegisterPair, value: u16) {
+        match pair {
+            InstructionRegisterPair::SP => self.set_stack_pointer(value),
+            InstructionRegisterPair::FA => {
+                self.change_register(InstructionRegister::A, (value >> 8) as i8);
+                self.set_flags_byte(value as u8);
+            }
+            _ => {
+                let registers = pair.get_registers();
+                self.change_register(registers.0, (value >> 8) as i8);
+                self.change_register(registers.1, value as i8);
+            }
+        }
+    }
+
     fn set_memory(&mut self, address: u16, value: i8) {
-        self.memory[address as usize] = value;
+        // writes to ROM are lost, like on the real bus
+        if self
+            .rom_regions
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&address))
+        {
+            return;
+        }
+
+        let size = self.memory.len();
+        let old_value = std::mem::replace(&mut self.memory[address as usize % size], value);
+
+        if let Some(changes) = self.memory_changes.as_mut() {
+            if old_value != value {
+                changes.retain(|(changed, _)| *changed != address);
+                changes.push((address, value));
+            }
+        }
+
+        if old_value != value {
+            self.redecode(address);
+        }
+
+        if self.watchpoints.contains(&address) {
+            self.watchpoint_hit = Some((address, value));
+        }
+    }
+
+    // the loaded program is only decoded once, code overwriting itself has to be decoded again,
+    // so every instruction covering address is decoded from memory as well as the instruction
+    // starting at address, e.g. one built in a DB buffer, data is decoded when it's fetched
+    fn redecode(&mut self, address: u16) {
+        for start in (0..3).map(|offset| address.wrapping_sub(offset)) {
+            let covered = match self.program.get(start as usize) {
+                Some(Some(Instruction::Data(_, _))) => false,
+                Some(Some(instruction)) => address.wrapping_sub(start) < instruction.get_size(),
+                Some(None) => start == address,
+                None => false,
+            };
+
+            if covered {
+                self.program[start as usize] = self.decode_at(start);
+            }
+        }
+    }
+
+    // the instruction starting at address, None if the bytes there don't decode
+    fn decode_at(&self, address: u16) -> Option<Instruction> {
+        let bytes: Vec<u8> = self
+            .memory_range(address, 3)
+            .iter()
+            .map(|byte| *byte as u8)
+            .collect();
+
+        Instruction::decode_one(&bytes)
+            .ok()
+            .map(|(instruction, _)| instruction)
     }
 
     fn get_memory(&self, address: u16) -> i8 {
-        self.memory[address as usize]
+        self.memory[address as usize % self.memory.len()]
+    }
+
+    // reads len bytes starting at start, addresses past 0xFFFF wrap around to 0 like the address bus
+    pub fn memory_range(&self, start: u16, len: u16) -> Vec<i8> {
+        (0..len)
+            .map(|offset| self.get_memory(start.wrapping_add(offset)))
+            .collect()
     }
 
     fn set_stack_pointer(&mut self, value: u16) {
         self.stack_pointer = value;
+
+        if let Some(guard) = &mut self.stack_guard {
+            if value < guard.floor || value > guard.ceiling {
+                (guard.hook)(value);
+            }
+        }
     }
 
     fn get_stack_pointer(&self) -> u16 {
         self.stack_pointer
     }
 
+    pub fn set_io_bus(&mut self, io_bus: Box<dyn IoBus>) {
+        self.io_bus = io_bus;
+    }
+
+    // handled like RST rst_vector before the next instruction, as long as interrupts are enabled
+    pub fn request_interrupt(&mut self, rst_vector: u8) {
+        if rst_vector > 7 {
+            panic!("invalid RST vector: {}", rst_vector);
+        }
+
+        self.pending_interrupt = Some(rst_vector);
+    }
+
+    fn handle_interrupt(&mut self) {
+        if !self.interrupts_enabled {
+            return;
+        }
+
+        if let Some(rst_vector) = self.pending_interrupt.take() {
+            // accepting an interrupt disables further interrupts until EI
+            self.interrupts_enabled = false;
+
+            self.call(self.get_program_counter(), rst_vector as u16 * 8);
+        }
+    }
+
+    // f is called before every instruction is executed
+    pub fn set_trace(&mut self, f: Box<dyn FnMut(&TraceRecord)>) {
+        self.tracer = Some(Tracer(f));
+    }
+
+    // useful to catch runaway recursion, f is called every time SP leaves floor..=ceiling
+    pub fn set_stack_guard(&mut self, floor: u16, ceiling: u16, f: Box<dyn FnMut(u16)>) {
+        self.stack_guard = Some(StackGuard {
+            floor,
+            ceiling,
+            hook: f,
+        });
+    }
+
+    pub fn clear_stack_guard(&mut self) {
+        self.stack_guard = None;
+    }
+
+    // a CALL of address runs f instead of the routine and continues right behind the call,
+    // e.g. to emulate the BDOS calls of CP/M programs at 0x0005, jumps to address aren't trapped
+    pub fn set_syscall_handler(&mut self, address: u16, f: SyscallFn) {
+        self.syscall_handlers.insert(address, SyscallHandler(f));
+    }
+
+    pub fn remove_syscall_handler(&mut self, address: u16) {
+        self.syscall_handlers.remove(&address);
+    }
+
+    // returns true if a handler for address ran, the call must not be taken then
+    fn handle_syscall(&mut self, address: u16) -> bool {
+        let register_c = self.get_register(InstructionRegister::C) as u8;
+        let register_de = self.register_pair(InstructionRegisterPair::DE);
+
+        match self.syscall_handlers.get_mut(&address) {
+            Some(handler) => (handler.0)(register_c, register_de, &self.memory),
+            None => return false,
+        }
+
+        true
+    }
+
+    // writes into start..=end are ignored from now on, load the ROM contents before
+    pub fn set_rom_region(&mut self, start: u16, end: u16) {
+        self.rom_regions.push((start, end));
+    }
+
+    pub fn clear_rom_regions(&mut self) {
+        self.rom_regions.clear();
+    }
+
+    fn trace(&mut self, instruction: &Instruction) {
+        let flags = self.flags();
+        if let Some(Tracer(f)) = &mut self.tracer {
+            f(&TraceRecord {
+                program_counter: self.program_counter,
+                instruction: instruction.clone(),
+                accumulator: self.registers[InstructionRegister::A.to_index() as usize],
+                flags,
+            });
+        }
+    }
+
+    pub fn load_program(&mut self, instructions: Vec<Option<Instruction>>) {
+        self.program = instructions;
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    // off by default, a jump to itself doesn't change anything, so it never ends
+    pub fn set_deadlock_detection(&mut self, enabled: bool) {
+        self.detect_deadlock = enabled;
+    }
+
+    // on by default, when disabled HLT works like NOP unless nothing follows it,
+    // so programs separated by HLT run one after the other
+    pub fn set_stop_on_halt(&mut self, enabled: bool) {
+        self.stop_on_halt = enabled;
+    }
+
+    pub fn add_watchpoint(&mut self, address: u16) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: u16) {
+        self.watchpoints.remove(&address);
+    }
+
+    // runs the loaded program until a breakpoint, a write to a watched address or HLT is reached,
+    // a breakpoint at start itself doesn't stop execution so it's possible to continue
+    pub fn run_until_break(&mut self, start: u16) -> BreakReason {
+        self.set_program_counter(start);
+        self.halted = false;
+        self.watchpoint_hit = None;
+
+        loop {
+            let address = self.get_program_counter();
+            match self.step() {
+                Ok(true) => (),
+                Ok(false) => return BreakReason::Halt,
+                Err(_) => return BreakReason::NoInstruction(self.get_program_counter()),
+            }
+
+            if self.detect_deadlock && self.get_program_counter() == address {
+                return BreakReason::Deadlock(address);
+            }
+
+            if let Some((address, value)) = self.watchpoint_hit.take() {
+                return BreakReason::Watchpoint(address, value);
+            }
+
+            if self.breakpoints.contains(&self.get_program_counter()) {
+                return BreakReason::Breakpoint(self.get_program_counter());
+            }
+        }
+    }
+
+    // runs the loaded program from entry like a CALL would, registers and stack have to be set up
+    // before, stops once the subroutine returns
+    pub fn call_subroutine(&mut self, entry: u16) -> RunOutcome {
+        let stack_pointer = self.get_stack_pointer();
+        self.set_memory(
+            stack_pointer.wrapping_sub(1),
+            (SUBROUTINE_SENTINEL >> 8) as i8,
+        );
+        self.set_memory(stack_pointer.wrapping_sub(2), SUBROUTINE_SENTINEL as i8);
+        self.set_stack_pointer(stack_pointer.wrapping_sub(2));
+
+        self.set_program_counter(entry);
+        self.halted = false;
+
+        loop {
+            let address = self.get_program_counter();
+            match self.step() {
+                Ok(true) => (),
+                Ok(false) => return RunOutcome::Halted(address),
+                Err(_) => return RunOutcome::NoInstruction(self.get_program_counter()),
+            }
+
+            // the subroutine is done once the return address is popped off the original stack
+            if self.get_program_counter() == SUBROUTINE_SENTINEL
+                && self.get_stack_pointer() == stack_pointer
+            {
+                return RunOutcome::Returned;
+            }
+        }
+    }
+
+    // runs the loaded program as long as the next instruction fits into budget t-states or until HLT,
+    // returns the t-states used
+    pub fn run_cycles(&mut self, start: u16, budget: u64) -> u64 {
+        self.set_program_counter(start);
+        self.halted = false;
+
+        let mut used = 0;
+        loop {
+            // an accepted interrupt runs an RST before the next instruction, it's charged on its own
+            if let (true, Some(rst_vector)) = (self.interrupts_enabled, self.pending_interrupt) {
+                let cost =
+                    cycles::t_states(&Instruction::Restart(InstructionCommand::Rst, rst_vector));
+                if used + cost > budget {
+                    return used;
+                }
+
+                used += cost;
+                self.handle_interrupt();
+            }
+
+            let instruction = match self.fetch() {
+                Ok(instruction) => instruction,
+                Err(_) => return used,
+            };
+            let cost = cycles::t_states(&instruction);
+            if used + cost > budget {
+                return used;
+            }
+
+            used += cost;
+            if !self.execute_fetched(&instruction) {
+                return used;
+            }
+        }
+    }
+
+    // executes the instruction of the loaded program at the program counter,
+    // returns the address and new value of every memory cell it changed
+    pub fn step_with_changes(&mut self) -> Result<Vec<(u16, i8)>, AssemblerError> {
+        self.memory_changes = Some(Vec::new());
+        let stepped = self.step();
+        let changes = self.memory_changes.take().unwrap_or_default();
+
+        stepped.map(|_| changes)
+    }
+
+    // executes one instruction of the loaded program, returns false after HLT
+    fn step(&mut self) -> Result<bool, AssemblerError> {
+        self.handle_interrupt();
+
+        let instruction = self.fetch()?;
+        Ok(self.execute_fetched(&instruction))
+    }
+
+    // the loaded program at the program counter, it's decoded again whenever memory under it changes
+    fn fetch(&self) -> Result<Instruction, AssemblerError> {
+        let address = self.get_program_counter();
+        let instruction = match self.program.get(address as usize) {
+            // the cpu doesn't know about DB and DW, their bytes run like any other code
+            Some(Some(Instruction::Data(_, _))) | Some(Some(Instruction::Origin(_, _))) => {
+                self.decode_at(address)
+            }
+            Some(Some(instruction)) => Some(instruction.clone()),
+            _ => None,
+        };
+
+        // no program loaded or the bytes there don't decode
+        instruction.ok_or_else(|| {
+            AssemblerError::InvalidInstruction(address, self.get_memory(address) as u8)
+        })
+    }
+
+    fn execute_fetched(&mut self, instruction: &Instruction) -> bool {
+        self.trace(instruction);
+
+        if self.stops_at(&self.program, instruction) {
+            self.halt(instruction);
+            return false;
+        }
+
+        if self.execute(instruction) == ProgramFlow::Next {
+            self.incr_program_counter(instruction);
+        }
+
+        true
+    }
+
+    // HLT stops unless stop_on_halt is disabled and there's another instruction after it
+    fn stops_at(&self, instructions: &[Option<Instruction>], instruction: &Instruction) -> bool {
+        if *instruction != Instruction::NoRegister(InstructionCommand::Hlt) {
+            return false;
+        }
+
+        let next = self
+            .get_program_counter()
+            .wrapping_add(instruction.get_size()) as usize;
+        self.stop_on_halt || !matches!(instructions.get(next), Some(Some(_)))
+    }
+
+    fn halt(&mut self, instruction: &Instruction) {
+        self.halted = true;
+        self.incr_program_counter(instruction);
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // address of the next instruction, unlike state this doesn't copy anything
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
     fn get_program_counter(&self) -> u16 {
         self.program_counter
     }
@@ -80,71 +651,103 @@ impl Cpu {
     }
 
     fn incr_program_counter(&mut self, instruction: &Instruction) {
-        self.set_program_counter(self.get_program_counter() + instruction.get_size() as u16);
+        // like the address bus, the program counter wraps around after 0xFFFF
+        self.set_program_counter(
+            self.get_program_counter()
+                .wrapping_add(instruction.get_size()),
+        );
     }
 
     pub fn run(&mut self, instructions: HashMap<u16, Instruction>, printing: bool) {
+        self.run_indexed(&assembler::index_by_address(instructions), printing);
+    }
+
+    // instructions are indexed by their address, see assembler::index_by_address
+    // without printing nothing is written to stdout, use is_halted and the accessors afterwards
+    pub fn run_indexed(&mut self, instructions: &[Option<Instruction>], printing: bool) {
         if printing {
-            self.print_run(instructions);
+            self.print_run(instructions, &mut std::io::stdout())
+                .unwrap();
             return;
         }
 
-        let mut instruction: &Instruction;
-
-        loop {
-            instruction = instructions.get(&self.get_program_counter()).unwrap();
+        self.run_program(instructions);
+    }
 
-            if let Instruction::NoRegister(command) = instruction {
-                if matches!(command, InstructionCommand::Hlt) {
-                    self.incr_program_counter(instruction);
-                    println!("Execution finished");
+    // for programs that don't start at 0, e.g. because of ORG 0x0100
+    pub fn run_program_from(
+        &mut self,
+        instructions: &[Option<Instruction>],
+        start: u16,
+    ) -> RunSummary {
+        self.set_program_counter(start);
+        self.run_program(instructions)
+    }
 
-                    println!("Final status: ");
-                    self.print_status();
-                    return;
-                }
-            }
+    // same as run_indexed without any output, execution stops after HLT,
+    // instructions become the loaded program, so code overwriting itself works like with step,
+    // panics when it reaches an address without an instruction
+    pub fn run_program(&mut self, instructions: &[Option<Instruction>]) -> RunSummary {
+        self.try_run_program(instructions)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
 
-            self.execute(instruction);
+    // same as run_program, reaching an address without an instruction is an error
+    pub fn try_run_program(
+        &mut self,
+        instructions: &[Option<Instruction>],
+    ) -> Result<RunSummary, AssemblerError> {
+        self.load_program(instructions.to_vec());
+        self.halted = false;
+        let mut executed = 0;
 
-            // jump instructions already adjust the program counter,
-            // no incrementing is needed
-            if !matches!(instruction, Instruction::Label(_, _)) {
-                self.incr_program_counter(instruction);
+        loop {
+            executed += 1;
+            if !self.step()? {
+                return Ok(RunSummary {
+                    instructions: executed,
+                    // HLT is a single byte
+                    halt_address: self.get_program_counter().wrapping_sub(1),
+                });
             }
         }
     }
 
-    pub fn print_run(&mut self, instructions: HashMap<u16, Instruction>) {
-        println!("Initial status:");
-        self.print_status();
+    pub fn print_run(
+        &mut self,
+        instructions: &[Option<Instruction>],
+        out: &mut dyn Write,
+    ) -> std::io::Result<()> {
+        self.load_program(instructions.to_vec());
+        self.halted = false;
+        writeln!(out, "Initial status:")?;
+        self.print_status(out)?;
 
-        let mut instruction: &Instruction;
         loop {
-            instruction = instructions.get(&self.get_program_counter()).unwrap();
-
-            println!("-------------");
-            println!("{:?}", instruction);
-
-            self.execute(instruction);
-            self.incr_program_counter(instruction);
-
-            if let Instruction::NoRegister(command) = instruction {
-                if matches!(command, InstructionCommand::Hlt) {
-                    println!("Execution finished");
-                    println!("Final status: ");
-                    self.print_status();
-                    return;
-                }
+            self.handle_interrupt();
+            let instruction = self.fetch().map_err(|error| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, error.to_string())
+            })?;
+
+            writeln!(out, "-------------")?;
+            writeln!(out, "{}", instruction)?;
+
+            // HLT has nothing to execute
+            if !self.execute_fetched(&instruction) {
+                writeln!(out, "Execution finished")?;
+                writeln!(out, "Final status: ")?;
+                self.print_status(out)?;
+                return Ok(());
             }
 
-            self.print_status();
+            self.print_status(out)?;
         }
     }
 
-    fn execute(&mut self, instruction: &Instruction) {
+    // jumps and returns set the program counter themselves
+    fn execute(&mut self, instruction: &Instruction) -> ProgramFlow {
         match instruction {
-            Instruction::NoRegister(command) => self.execute_no_reg_instruction(command),
+            Instruction::NoRegister(command) => return self.execute_no_reg_instruction(command),
             Instruction::SingleRegister(command, register) => {
                 self.execute_single_reg_instruction(command, register)
             }
@@ -167,13 +770,21 @@ impl Cpu {
                 self.execute_pair_reg_instruction(command, register_pair)
             }
             Instruction::Label(command, address) => {
-                self.execute_label_instruction(command, *address)
+                return self.execute_label_instruction(command, *address)
+            }
+            Instruction::Restart(_, number) => return self.execute_rst(*number),
+            Instruction::Data(_, _) | Instruction::Origin(_, _) => {
+                unreachable!("fetch decodes assembler directives from memory")
             }
         }
+
+        ProgramFlow::Next
     }
 
-    fn execute_no_reg_instruction(&mut self, command: &InstructionCommand) {
+    fn execute_no_reg_instruction(&mut self, command: &InstructionCommand) -> ProgramFlow {
         match command {
+            // HLT only gets here with stop_on_halt disabled
+            InstructionCommand::Nop | InstructionCommand::Hlt => (),
             InstructionCommand::Stc => self.execute_stc(),
             InstructionCommand::Cmc => self.execute_cmc(),
             InstructionCommand::Cma => self.execute_cma(),
@@ -185,9 +796,32 @@ impl Cpu {
             InstructionCommand::Xchg => self.execute_xchg(),
             InstructionCommand::Sphl => self.execute_sphl(),
             InstructionCommand::Xthl => self.execute_xthl(),
-            InstructionCommand::Pchl => self.execute_pchl(),
+            InstructionCommand::Pchl => {
+                self.execute_pchl();
+                return ProgramFlow::Jump;
+            }
+            InstructionCommand::Ret => {
+                self.execute_ret();
+                return ProgramFlow::Jump;
+            }
+            InstructionCommand::Rc => return self.return_if(self.get_flag(Flag::C)),
+            InstructionCommand::Rnc => return self.return_if(!self.get_flag(Flag::C)),
+            InstructionCommand::Rz => return self.return_if(self.get_flag(Flag::Z)),
+            InstructionCommand::Rnz => return self.return_if(!self.get_flag(Flag::Z)),
+            InstructionCommand::Rm => return self.return_if(self.get_flag(Flag::S)),
+            InstructionCommand::Rp => return self.return_if(!self.get_flag(Flag::S)),
+            InstructionCommand::Rpe => return self.return_if(self.get_flag(Flag::P)),
+            InstructionCommand::Rpo => return self.return_if(!self.get_flag(Flag::P)),
+            InstructionCommand::Ei => self.execute_ei(),
+            InstructionCommand::Di => self.execute_di(),
+            #[cfg(feature = "i8085")]
+            InstructionCommand::Rim => self.execute_rim(),
+            #[cfg(feature = "i8085")]
+            InstructionCommand::Sim => self.execute_sim(),
             _ => panic!("invalid instruction"),
         }
+
+        ProgramFlow::Next
     }
 
     fn execute_single_reg_instruction(
@@ -231,6 +865,8 @@ impl Cpu {
             InstructionCommand::Ani => self.execute_ani(intermediate),
             InstructionCommand::Cpi => self.execute_cpi(intermediate),
             InstructionCommand::Sbi => self.execute_sbi(intermediate),
+            InstructionCommand::In => self.execute_in(intermediate as u8),
+            InstructionCommand::Out => self.execute_out(intermediate as u8),
             _ => panic!("invalid instruction"),
         }
     }
@@ -290,7 +926,11 @@ impl Cpu {
         }
     }
 
-    fn execute_label_instruction(&mut self, command: &InstructionCommand, address: u16) {
+    fn execute_label_instruction(
+        &mut self,
+        command: &InstructionCommand,
+        address: u16,
+    ) -> ProgramFlow {
         match command {
             InstructionCommand::Jmp => self.execute_jmp(address),
             InstructionCommand::Jc => self.execute_jc(address),
@@ -301,6 +941,15 @@ impl Cpu {
             InstructionCommand::Jp => self.execute_jp(address),
             InstructionCommand::Jpe => self.execute_jpe(address),
             InstructionCommand::Jpo => self.execute_jpo(address),
+            InstructionCommand::Call => self.call_if(true, address),
+            InstructionCommand::Cc => self.call_if(self.get_flag(Flag::C), address),
+            InstructionCommand::Cnc => self.call_if(!self.get_flag(Flag::C), address),
+            InstructionCommand::Cz => self.call_if(self.get_flag(Flag::Z), address),
+            InstructionCommand::Cnz => self.call_if(!self.get_flag(Flag::Z), address),
+            InstructionCommand::Cm => self.call_if(self.get_flag(Flag::S), address),
+            InstructionCommand::Cp => self.call_if(!self.get_flag(Flag::S), address),
+            InstructionCommand::Cpe => self.call_if(self.get_flag(Flag::P), address),
+            InstructionCommand::Cpo => self.call_if(!self.get_flag(Flag::P), address),
             _ => panic!("invalid instruction"),
         }
     }
@@ -313,17 +962,13 @@ impl Cpu {
         let current_a = self.get_register(InstructionRegister::A);
         let new_a = current_a.wrapping_add(intermediate);
 
-        if self.get_register(InstructionRegister::A) == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(new_a);
 
-        if self.get_register(InstructionRegister::A) < 0 {
-            self.set_flag(Flag::S, true);
-        } else {
-            self.set_flag(Flag::S, false);
-        }
+        // carry out of bit 3
+        self.set_flag(
+            Flag::A,
+            (intermediate as u8 & 15) + (current_a as u8 & 15) > 15,
+        );
 
         // if onecomplement representation added > 255 -> carry exists
         // example: 127 + 127
@@ -343,17 +988,13 @@ impl Cpu {
             .wrapping_add(intermediate)
             .wrapping_add(self.get_flag(Flag::C) as i8);
 
-        if self.get_register(InstructionRegister::A) == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(new_a);
 
-        if self.get_register(InstructionRegister::A) < 0 {
-            self.set_flag(Flag::S, true);
-        } else {
-            self.set_flag(Flag::S, false);
-        }
+        // carry out of bit 3
+        self.set_flag(
+            Flag::A,
+            (intermediate as u8 & 15) + (current_a as u8 & 15) + self.get_flag(Flag::C) as u8 > 15,
+        );
 
         // if onecomplement representation added > 255 -> carry exists
         // example: 127 + 127
@@ -375,31 +1016,23 @@ impl Cpu {
 
         self.change_register(InstructionRegister::A, new_a);
 
-        if self.get_register(InstructionRegister::A) == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(new_a);
 
-        if self.get_register(InstructionRegister::A) < 0 {
-            self.set_flag(Flag::S, true);
-        } else {
-            self.set_flag(Flag::S, false);
-        }
+        // like compare, the 8080 subtracts by adding the two's complement
+        self.set_flag(
+            Flag::A,
+            (current_a as u8 & 15) + (!intermediate as u8 & 15) + 1 > 15,
+        );
 
-        // if onecomplement representation subtraction < 0 -> set carry
-        // "x as u8 as u16" converts to onecomplement representation
-        if (current_a as u8 as u16).checked_sub(intermediate as u8 as u16) != None {
-            self.set_flag(Flag::C, false);
-        } else {
-            self.set_flag(Flag::C, true);
-        }
+        // carry is set on borrow, compared as unsigned bytes
+        self.set_flag(Flag::C, (current_a as u8) < (intermediate as u8));
     }
 
+    // MOV D, S copies S into D, like every other 8080 instruction the destination comes first
     fn execute_mov(&mut self, args: &(InstructionRegister, InstructionRegister)) {
-        let source_value = self.get_register(args.0);
+        let source_value = self.get_register(args.1);
 
-        self.change_register(args.1, source_value);
+        self.change_register(args.0, source_value);
     }
 
     fn execute_add(&mut self, arg: &InstructionRegister) {
@@ -407,19 +1040,13 @@ impl Cpu {
         let current_a = self.get_register(InstructionRegister::A);
         let new_a = current_a.wrapping_add(source_value);
 
-        self.change_register(InstructionRegister::A, new_a);
-
-        if self.get_register(InstructionRegister::A) == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(new_a);
 
-        if self.get_register(InstructionRegister::A) < 0 {
-            self.set_flag(Flag::S, true);
-        } else {
-            self.set_flag(Flag::S, false);
-        }
+        // carry out of bit 3
+        self.set_flag(
+            Flag::A,
+            (source_value as u8 & 15) + (current_a as u8 & 15) > 15,
+        );
 
         // if onecomplement representation added > 255 -> carry exists
         // example: 127 + 127
@@ -437,19 +1064,17 @@ impl Cpu {
         let source_value = self.get_register(*arg);
         let current_a = self.get_register(InstructionRegister::A);
 
-        let new_a = current_a + source_value + self.get_flag(Flag::C) as i8;
+        let new_a = current_a
+            .wrapping_add(source_value)
+            .wrapping_add(self.get_flag(Flag::C) as i8);
 
-        if self.get_register(InstructionRegister::A) == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(new_a);
 
-        if self.get_register(InstructionRegister::A) < 0 {
-            self.set_flag(Flag::S, true);
-        } else {
-            self.set_flag(Flag::S, false);
-        }
+        // carry out of bit 3
+        self.set_flag(
+            Flag::A,
+            (source_value as u8 & 15) + (current_a as u8 & 15) + self.get_flag(Flag::C) as u8 > 15,
+        );
 
         // if onecomplement representation added > 255 -> carry exists
         // example: 127 + 127
@@ -472,68 +1097,51 @@ impl Cpu {
 
         self.change_register(InstructionRegister::A, new_a);
 
-        if self.get_register(InstructionRegister::A) == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(new_a);
 
-        if self.get_register(InstructionRegister::A) < 0 {
-            self.set_flag(Flag::S, true);
-        } else {
-            self.set_flag(Flag::S, false);
-        }
+        // like compare, the 8080 subtracts by adding the two's complement
+        self.set_flag(
+            Flag::A,
+            (current_a as u8 & 15) + (!source_value as u8 & 15) + 1 > 15,
+        );
 
-        // if onecomplement representation subtraction < 0 -> set carry
-        // "x as u8 as u16" converts to onecomplement representation
-        if (current_a as u8 as u16).checked_sub(source_value as u8 as u16) != None {
-            self.set_flag(Flag::C, false);
-        } else {
-            self.set_flag(Flag::C, true);
-        }
+        // carry is set on borrow, compared as unsigned bytes
+        self.set_flag(Flag::C, (current_a as u8) < (source_value as u8));
     }
 
+    // carry is left alone, only the auxiliary carry changes
     fn execute_inr(&mut self, arg: &InstructionRegister) {
-        let new_value = self.get_register(*arg) + 1;
+        let value = self.get_register(*arg);
+        let new_value = value.wrapping_add(1);
 
         self.change_register(*arg, new_value);
+        self.update_szp(new_value);
 
-        if self.get_register(InstructionRegister::A) == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
-
-        if self.get_register(InstructionRegister::A) < 0 {
-            self.set_flag(Flag::S, true);
-        } else {
-            self.set_flag(Flag::S, false);
-        }
+        // carry out of bit 3
+        self.set_flag(Flag::A, value & 15 == 15);
     }
 
     fn execute_dcr(&mut self, arg: &InstructionRegister) {
-        let new_value = self.get_register(*arg) - 1;
+        let value = self.get_register(*arg);
+        let new_value = value.wrapping_sub(1);
 
         self.change_register(*arg, new_value);
+        self.update_szp(new_value);
 
-        if self.get_register(InstructionRegister::A) == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
-
-        if self.get_register(InstructionRegister::A) < 0 {
-            self.set_flag(Flag::S, true);
-        } else {
-            self.set_flag(Flag::S, false);
-        }
-    }
+        // the 8080 adds 0xFF, which carries out of bit 3 unless the low nibble is 0
+        self.set_flag(Flag::A, value & 15 != 0);
+    }
 
     fn execute_ana(&mut self, arg: &InstructionRegister) {
         let acc = self.get_register(InstructionRegister::A);
         let reg = self.get_register(*arg);
 
         self.change_register(InstructionRegister::A, acc & reg);
+        self.update_szp(acc & reg);
+
+        // the 8080 sets the auxiliary carry to bit 3 of either operand
+        self.set_flag(Flag::A, (acc | reg) & 0b1000 != 0);
+        self.set_flag(Flag::C, false);
     }
 
     fn set_flag(&mut self, flag: Flag, value: bool) {
@@ -544,6 +1152,55 @@ impl Cpu {
         self.flags[flag.get_index()]
     }
 
+    // packs the flags in the PSW layout S Z 0 A 0 P 1 C
+    pub fn flags_byte(&self) -> u8 {
+        let mut flags = 0b0000_0010;
+        flags |= (self.get_flag(Flag::S) as u8) << 7;
+        flags |= (self.get_flag(Flag::Z) as u8) << 6;
+        flags |= (self.get_flag(Flag::A) as u8) << 4;
+        flags |= (self.get_flag(Flag::P) as u8) << 2;
+        flags |= self.get_flag(Flag::C) as u8;
+        flags
+    }
+
+    pub fn set_flags_byte(&mut self, flags: u8) {
+        self.set_flag(Flag::S, (flags >> 7) & 1 != 0);
+        self.set_flag(Flag::Z, (flags >> 6) & 1 != 0);
+        self.set_flag(Flag::A, (flags >> 4) & 1 != 0);
+        self.set_flag(Flag::P, (flags >> 2) & 1 != 0);
+        self.set_flag(Flag::C, flags & 1 != 0);
+    }
+
+    pub fn flags(&self) -> Flags {
+        Flags {
+            sign: self.get_flag(Flag::S),
+            zero: self.get_flag(Flag::Z),
+            aux_carry: self.get_flag(Flag::A),
+            parity: self.get_flag(Flag::P),
+            carry: self.get_flag(Flag::C),
+        }
+    }
+
+    fn set_flags(&mut self, flags: Flags) {
+        self.set_flag(Flag::S, flags.sign);
+        self.set_flag(Flag::Z, flags.zero);
+        self.set_flag(Flag::A, flags.aux_carry);
+        self.set_flag(Flag::P, flags.parity);
+        self.set_flag(Flag::C, flags.carry);
+    }
+
+    // only the flags packed as 0b000S_ZAPC, handy to check every flag in a single comparison
+    pub fn flags_as_mask(&self) -> u8 {
+        Flag::iter().fold(0, |mask, flag| (mask << 1) | self.get_flag(flag) as u8)
+    }
+
+    // the same layout as flags_as_mask, the upper three bits are ignored
+    pub fn set_flags_from_mask(&mut self, mask: u8) {
+        for (i, flag) in Flag::iter().enumerate() {
+            self.set_flag(flag, (mask >> (4 - i)) & 1 != 0);
+        }
+    }
+
     fn execute_stc(&mut self) {
         self.set_flag(Flag::C, true);
     }
@@ -643,99 +1300,72 @@ impl Cpu {
         acc |= self.get_register(*arg);
 
         self.change_register(InstructionRegister::A, acc);
+        self.update_szp(acc);
+        self.set_flag(Flag::A, false);
         self.set_flag(Flag::C, false);
     }
 
     fn execute_daa(&mut self) {
-        let mut acc = self.get_register(InstructionRegister::A);
+        let acc = self.get_register(InstructionRegister::A) as u8;
+        let mut correction = 0;
 
-        // check if 4 least significant bits are > 9
+        // the low nibble is corrected if it's > 9 or the last addition carried out of it
         if (acc & 15) > 9 || self.get_flag(Flag::A) {
-            acc = acc.wrapping_add(6);
-
-            // check if carry out happens
-            if (self.get_register(InstructionRegister::A) & -16) != (acc & -16) {
-                self.set_flag(Flag::A, true);
-            } else {
-                self.set_flag(Flag::A, false);
-            }
+            correction |= 0x06;
         }
 
-        // check if 4 most significant bits are > 9
-        // increment 4 most significant bits by 6
-        // since its 4 most significant, +6 = +96
-        let most_significant_bits = (((acc & -16) as u8) >> 4) as i8;
-        if most_significant_bits > 9 || self.get_flag(Flag::C) {
-            // if onecomplement representation added > 255 -> carry exists
-            // example: 127 + 127
-            // "x as u8 as u16" converts to onecomplement representation
-            if (acc as u8 as u16) + (96u16) > 255 {
-                self.set_flag(Flag::C, true);
-            } else {
-                self.set_flag(Flag::C, false);
-            }
-            acc = acc.wrapping_add(96);
+        // the high nibble is corrected if the whole value is > 0x99, the carry is never cleared
+        if acc > 0x99 || self.get_flag(Flag::C) {
+            correction |= 0x60;
+            self.set_flag(Flag::C, true);
         }
 
+        // adding 6 carries out of the low nibble exactly when it was > 9
+        self.set_flag(Flag::A, (acc & 15) + (correction & 15) > 15);
+
+        let acc = acc.wrapping_add(correction) as i8;
         self.change_register(InstructionRegister::A, acc);
+        self.update_szp(acc);
     }
 
     fn execute_stax(&mut self, register_pair: &InstructionRegisterPair) {
-        let registers = register_pair.get_registers();
-        let mut first_register = self.get_register(registers.0) as u16;
-        let mut second_register = self.get_register(registers.1) as u16;
+        let address = self.register_pair(*register_pair);
         let acc = self.get_register(InstructionRegister::A);
 
-        // make sure first 8 bits are 0 because of negative numbers
-        second_register &= 255;
-
-        first_register <<= 8;
-
-        let address = first_register | second_register;
         self.set_memory(address, acc);
     }
 
     fn execute_ldax(&mut self, register_pair: &InstructionRegisterPair) {
-        let registers = register_pair.get_registers();
-        let mut first_register = self.get_register(registers.0) as u16;
-        let mut second_register = self.get_register(registers.1) as u16;
-
-        // make sure first 8 bits are 0 because of negative numbers
-        second_register &= 255;
+        let address = self.register_pair(*register_pair);
 
-        first_register <<= 8;
-
-        let address = first_register | second_register;
         self.change_register(InstructionRegister::A, self.get_memory(address));
     }
 
     fn execute_cmp(&mut self, register: &InstructionRegister) {
-        let acc = self.get_register(InstructionRegister::A);
         let reg = self.get_register(*register);
+        self.compare(reg);
+    }
 
-        let result = acc.wrapping_sub(reg);
+    // subtracts value from the accumulator for the flags only, shared by CMP and CPI
+    fn compare(&mut self, value: i8) {
+        let acc = self.get_register(InstructionRegister::A) as u8;
+        let value = value as u8;
+        let result = acc.wrapping_sub(value);
 
-        if result == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(result as i8);
 
-        // "x as u8 as u16" converts to onecomplement representation
-        // if onecomplement representation subtraction < 0 -> carry happens
-        // only works if subtraction is happening, if reg is negative,
-        // comparision with 255 has to be done
-        if reg < 0 {
-            if ((acc as u8 as u16) + (reg as u8 as u16)) > 255 {
-                self.set_flag(Flag::C, false);
-            } else {
-                self.set_flag(Flag::C, true);
-            }
-        } else if (acc as u8 as u16).checked_sub(reg as u8 as u16) == None {
-            self.set_flag(Flag::C, false);
-        } else {
-            self.set_flag(Flag::C, true);
-        }
+        // the 8080 subtracts by adding the two's complement
+        self.set_flag(Flag::A, (acc & 15) + (!value & 15) + 1 > 15);
+
+        // carry is set when the accumulator is smaller, compared as unsigned bytes
+        self.set_flag(Flag::C, acc < value);
+    }
+
+    // sign, zero and parity always describe the result of the operation
+    fn update_szp(&mut self, value: i8) {
+        self.set_flag(Flag::S, value < 0);
+        self.set_flag(Flag::Z, value == 0);
+        self.set_flag(Flag::P, value.count_ones().is_multiple_of(2));
     }
 
     fn execute_xra(&mut self, register: &InstructionRegister) {
@@ -744,36 +1374,34 @@ impl Cpu {
 
         let result = acc ^ reg;
 
-        if result == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(result);
+        self.set_flag(Flag::A, false);
+        self.set_flag(Flag::C, false);
 
-        self.change_register(*register, result);
+        self.change_register(InstructionRegister::A, result);
     }
 
     fn execute_sbb(&mut self, register: &InstructionRegister) {
-        let acc = self.get_register(InstructionRegister::A);
-        let mut reg = self.get_register(*register);
+        self.subtract_with_borrow(self.get_register(*register) as u8);
+    }
 
-        reg = reg.wrapping_add(self.get_flag(Flag::C) as i8);
+    // shared by SBB and SBI
+    fn subtract_with_borrow(&mut self, reg: u8) {
+        let acc = self.get_register(InstructionRegister::A) as u8;
+        let carry = self.get_flag(Flag::C) as u8;
 
-        let result = acc.wrapping_sub(reg);
+        // operand and carry are subtracted separately, so -128 never has to be negated
+        let result = acc.wrapping_sub(reg).wrapping_sub(carry);
 
-        if result == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(result as i8);
 
-        if (acc as u8).checked_add(-reg as u8) == None {
-            self.set_flag(Flag::C, false);
-        } else {
-            self.set_flag(Flag::C, true);
-        }
+        // the 8080 adds the complement of the operand and the inverted carry
+        self.set_flag(Flag::A, (acc & 15) + (!reg & 15) + (1 - carry) > 15);
 
-        self.change_register(InstructionRegister::A, result);
+        // borrow is needed when operand and carry don't fit into the accumulator
+        self.set_flag(Flag::C, (acc as u16) < (reg as u16) + (carry as u16));
+
+        self.change_register(InstructionRegister::A, result as i8);
     }
 
     fn execute_xchg(&mut self) {
@@ -789,16 +1417,7 @@ impl Cpu {
     }
 
     fn execute_sphl(&mut self) {
-        let mut reg_h = self.get_register(InstructionRegister::H) as u16;
-        let mut reg_l = self.get_register(InstructionRegister::L) as u16;
-
-        // make sure first 8 bits are 0 because of negative numbers
-        reg_l &= 255;
-
-        reg_h <<= 8;
-
-        let stack_pointer = reg_l | reg_h;
-        self.set_stack_pointer(stack_pointer);
+        self.set_stack_pointer(self.hl_address());
     }
 
     fn execute_xthl(&mut self) {
@@ -806,91 +1425,30 @@ impl Cpu {
         let reg_h = self.get_register(InstructionRegister::H);
 
         let memory = self.get_memory(self.get_stack_pointer());
-        let memory_incr = self.get_memory(self.get_stack_pointer() + 1);
+        let memory_incr = self.get_memory(self.get_stack_pointer().wrapping_add(1));
 
         self.change_register(InstructionRegister::L, memory);
         self.change_register(InstructionRegister::H, memory_incr);
         self.set_memory(self.get_stack_pointer(), reg_l);
-        self.set_memory(self.get_stack_pointer() + 1, reg_h);
+        self.set_memory(self.get_stack_pointer().wrapping_add(1), reg_h);
     }
 
     fn execute_dcx(&mut self, register_pair: &InstructionRegisterPair) {
-        if matches!(register_pair, InstructionRegisterPair::SP) {
-            self.set_stack_pointer(self.get_stack_pointer().wrapping_sub(1));
-            return;
-        }
-
-        let registers = register_pair.get_registers();
-
-        let mut first_register = self.get_register(registers.0) as u16;
-        let mut second_register = self.get_register(registers.1) as u16;
-
-        // make sure first 8 bits are 0 because of negative numbers
-        second_register &= 255;
-
-        first_register <<= 8;
-
-        let mut value = first_register | second_register;
-        value = value.wrapping_sub(1);
-
-        self.change_register(registers.0, (value >> 8) as i8);
-        self.change_register(registers.1, (value & 255) as i8);
+        let value = self.register_pair(*register_pair).wrapping_sub(1);
+        self.set_register_pair(*register_pair, value);
     }
 
     fn execute_inx(&mut self, register_pair: &InstructionRegisterPair) {
-        if matches!(register_pair, InstructionRegisterPair::SP) {
-            self.set_stack_pointer(self.get_stack_pointer().wrapping_add(1));
-            return;
-        }
-
-        let registers = register_pair.get_registers();
-
-        let mut first_register = self.get_register(registers.0) as u16;
-        let mut second_register = self.get_register(registers.1) as u16;
-
-        // make sure first 8 bits are 0 because of negative numbers
-        second_register &= 255;
-
-        first_register <<= 8;
-
-        let mut value = first_register | second_register;
-        value = value.wrapping_add(1);
-
-        self.change_register(registers.0, (value >> 8) as i8);
-        self.change_register(registers.1, (value & 255) as i8);
+        let value = self.register_pair(*register_pair).wrapping_add(1);
+        self.set_register_pair(*register_pair, value);
     }
 
     fn execute_dad(&mut self, register_pair: &InstructionRegisterPair) {
-        let value: u16;
-        if matches!(register_pair, InstructionRegisterPair::SP) {
-            value = self.get_stack_pointer();
-        } else {
-            let registers = register_pair.get_registers();
-
-            let mut first_register = self.get_register(registers.0) as u16;
-            let mut second_register = self.get_register(registers.1) as u16;
-
-            // make sure first 8 bits are 0 because of negative numbers
-            second_register &= 255;
-
-            first_register <<= 8;
-
-            value = first_register | second_register;
-        }
-
-        let mut h_register = self.get_register(InstructionRegister::H) as u16;
-        let mut l_register = self.get_register(InstructionRegister::L) as u16;
-
-        // make sure first 8 bits are 0 because of negative numbers
-        l_register &= 255;
-
-        h_register <<= 8;
-
-        let hl_value = h_register | l_register;
+        let value = self.register_pair(*register_pair);
+        let hl_value = self.hl_address();
         let result = value.wrapping_add(hl_value);
 
-        self.change_register(InstructionRegister::H, (result >> 8) as i8);
-        self.change_register(InstructionRegister::L, (result & 255) as i8);
+        self.set_register_pair(InstructionRegisterPair::HL, result);
 
         if ((value as u32) + (hl_value as u32)) > 65535 {
             self.set_flag(Flag::C, true);
@@ -900,57 +1458,22 @@ impl Cpu {
     }
 
     fn execute_push(&mut self, register_pair: &InstructionRegisterPair) {
-        let first_register: i8;
-        let mut second_register: i8;
-
-        if matches!(register_pair, InstructionRegisterPair::FA) {
-            first_register = self.get_register(InstructionRegister::A);
-
-            second_register = 2;
-            second_register |= (self.get_flag(Flag::S) as i8) << 7;
-            second_register |= (self.get_flag(Flag::Z) as i8) << 6;
-            second_register |= (self.get_flag(Flag::A) as i8) << 4;
-            second_register |= (self.get_flag(Flag::P) as i8) << 2;
-            second_register |= self.get_flag(Flag::C) as i8;
-        } else {
-            let registers = register_pair.get_registers();
-            first_register = self.get_register(registers.0);
-            second_register = self.get_register(registers.1);
-        }
-
+        let value = self.register_pair(*register_pair);
         let stack_pointer = self.get_stack_pointer();
 
-        self.set_memory(stack_pointer.wrapping_sub(1), first_register);
-        self.set_memory(stack_pointer.wrapping_sub(2), second_register);
+        self.set_memory(stack_pointer.wrapping_sub(1), (value >> 8) as i8);
+        self.set_memory(stack_pointer.wrapping_sub(2), value as i8);
         self.set_stack_pointer(stack_pointer.wrapping_sub(2));
     }
 
     fn execute_pop(&mut self, register_pair: &InstructionRegisterPair) {
-        if !matches!(register_pair, &InstructionRegisterPair::FA) {
-            let registers = register_pair.get_registers();
-
-            let stack_pointer = self.get_stack_pointer();
-            self.change_register(registers.1, self.get_memory(self.get_stack_pointer()));
-            self.change_register(
-                registers.0,
-                self.get_memory(self.get_stack_pointer().wrapping_add(1)),
-            );
-            self.set_stack_pointer(stack_pointer.wrapping_add(2));
-            return;
-        }
-
         let stack_pointer = self.get_stack_pointer();
-        self.change_register(
-            InstructionRegister::A,
+        let value = pair_to_address(
             self.get_memory(stack_pointer.wrapping_add(1)),
+            self.get_memory(stack_pointer),
         );
 
-        let flags = self.get_memory(stack_pointer);
-        self.set_flag(Flag::S, (flags >> 7) != 0);
-        self.set_flag(Flag::Z, ((flags >> 6) & 1) != 0);
-        self.set_flag(Flag::A, ((flags >> 4) & 1) != 0);
-        self.set_flag(Flag::P, ((flags >> 2) & 1) != 0);
-        self.set_flag(Flag::C, (flags & 1) != 0);
+        self.set_register_pair(*register_pair, value);
         self.set_stack_pointer(stack_pointer.wrapping_add(2));
     }
 
@@ -959,13 +1482,9 @@ impl Cpu {
         acc |= intermediate;
 
         self.change_register(InstructionRegister::A, acc);
+        self.set_flag(Flag::A, false);
         self.set_flag(Flag::C, false);
-
-        if acc == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(acc);
     }
 
     fn execute_xri(&mut self, intermediate: i8) {
@@ -973,13 +1492,9 @@ impl Cpu {
         acc ^= intermediate;
 
         self.change_register(InstructionRegister::A, acc);
+        self.set_flag(Flag::A, false);
         self.set_flag(Flag::C, false);
-
-        if acc == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(acc);
     }
 
     fn execute_ani(&mut self, intermediate: i8) {
@@ -987,63 +1502,29 @@ impl Cpu {
         let result = acc & intermediate;
 
         self.change_register(InstructionRegister::A, result);
+        // like ANA, bit 3 of either operand
+        self.set_flag(Flag::A, (acc | intermediate) & 0b1000 != 0);
         self.set_flag(Flag::C, false);
 
-        if result == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
+        self.update_szp(result);
     }
 
     fn execute_cpi(&mut self, intermediate: i8) {
-        let acc = self.get_register(InstructionRegister::A);
-
-        let result = acc.wrapping_sub(intermediate);
-
-        if result == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
-
-        if (intermediate < 0 && acc >= 0) || (intermediate >= 0 && acc < 0) {
-            if self.get_flag(Flag::C) {
-                self.set_flag(Flag::C, false);
-            } else {
-                self.set_flag(Flag::C, true);
-            }
+        self.compare(intermediate);
+    }
 
-            return;
-        }
+    fn execute_sbi(&mut self, intermediate: i8) {
+        self.subtract_with_borrow(intermediate as u8);
+    }
 
-        if intermediate > acc {
-            self.set_flag(Flag::C, true);
-        } else {
-            self.set_flag(Flag::C, false);
-        }
+    fn execute_in(&mut self, port: u8) {
+        let value = self.io_bus.read(port);
+        self.change_register(InstructionRegister::A, value as i8);
     }
 
-    fn execute_sbi(&mut self, mut intermediate: i8) {
+    fn execute_out(&mut self, port: u8) {
         let acc = self.get_register(InstructionRegister::A);
-
-        intermediate = intermediate.wrapping_add(self.get_flag(Flag::C) as i8);
-
-        let result = acc.wrapping_sub(intermediate);
-
-        if result == 0 {
-            self.set_flag(Flag::Z, true);
-        } else {
-            self.set_flag(Flag::Z, false);
-        }
-
-        if (acc as u8).checked_add(-intermediate as u8) == None {
-            self.set_flag(Flag::C, false);
-        } else {
-            self.set_flag(Flag::C, true);
-        }
-
-        self.change_register(InstructionRegister::A, result);
+        self.io_bus.write(port, acc as u8);
     }
 
     fn execute_lxi(&mut self, register_pair: &InstructionRegisterPair, intermediate: i16) {
@@ -1074,7 +1555,7 @@ impl Cpu {
             self.get_register(InstructionRegister::L),
         );
         self.set_memory(
-            intermediate as u16 + 1,
+            (intermediate as u16).wrapping_add(1),
             self.get_register(InstructionRegister::H),
         );
     }
@@ -1083,218 +1564,671 @@ impl Cpu {
         self.change_register(InstructionRegister::L, self.get_memory(intermediate as u16));
         self.change_register(
             InstructionRegister::H,
-            self.get_memory(intermediate as u16 + 1),
+            self.get_memory((intermediate as u16).wrapping_add(1)),
         );
     }
 
+    fn execute_ei(&mut self) {
+        self.interrupts_enabled = true;
+    }
+
+    fn execute_di(&mut self) {
+        self.interrupts_enabled = false;
+    }
+
+    #[cfg(feature = "i8085")]
+    pub fn set_serial_input(&mut self, value: bool) {
+        self.i8085.serial_input = value;
+    }
+
+    #[cfg(feature = "i8085")]
+    pub fn get_serial_output(&self) -> bool {
+        self.i8085.serial_output
+    }
+
+    // SID IE M7.5 M6.5 M5.5 in bits 7, 3, 2, 1, 0, pending interrupts are not modelled
+    #[cfg(feature = "i8085")]
+    fn execute_rim(&mut self) {
+        let mut acc = self.i8085.interrupt_masks & 7;
+        acc |= (self.interrupts_enabled as u8) << 3;
+        acc |= (self.i8085.serial_input as u8) << 7;
+
+        self.change_register(InstructionRegister::A, acc as i8);
+    }
+
+    // SOD SOE x R7.5 MSE M7.5 M6.5 M5.5
+    #[cfg(feature = "i8085")]
+    fn execute_sim(&mut self) {
+        let acc = self.get_register(InstructionRegister::A) as u8;
+
+        // mask set enable
+        if acc & 0b0000_1000 != 0 {
+            self.i8085.interrupt_masks = acc & 7;
+        }
+
+        // serial output enable
+        if acc & 0b0100_0000 != 0 {
+            self.i8085.serial_output = acc & 0b1000_0000 != 0;
+        }
+    }
+
     fn execute_pchl(&mut self) {
-        let reg_h = self.get_register(InstructionRegister::H) as i16;
-        let reg_l = self.get_register(InstructionRegister::L) as i16;
-        let counter = (reg_h << 8) + (reg_l & 255);
+        self.set_program_counter(self.hl_address());
+    }
+
+    fn execute_ret(&mut self) {
+        let stack_pointer = self.get_stack_pointer();
+        let low = self.get_memory(stack_pointer);
+        let high = self.get_memory(stack_pointer.wrapping_add(1));
 
-        self.set_program_counter(counter as u16);
+        self.set_program_counter(pair_to_address(high, low));
+        self.set_stack_pointer(stack_pointer.wrapping_add(2));
     }
 
-    fn execute_jmp(&mut self, address: u16) {
+    fn execute_jmp(&mut self, address: u16) -> ProgramFlow {
         self.set_program_counter(address);
+        ProgramFlow::Jump
     }
 
-    fn execute_jc(&mut self, address: u16) {
-        if self.get_flag(Flag::C) {
-            self.set_program_counter(address);
+    // a jump that isn't taken continues with the next instruction
+    fn jump_if(&mut self, condition: bool, address: u16) -> ProgramFlow {
+        if !condition {
+            return ProgramFlow::Next;
         }
+
+        self.set_program_counter(address);
+        ProgramFlow::Jump
     }
 
-    fn execute_jnc(&mut self, address: u16) {
-        if !self.get_flag(Flag::C) {
-            self.set_program_counter(address);
-        }
+    fn execute_jc(&mut self, address: u16) -> ProgramFlow {
+        self.jump_if(self.get_flag(Flag::C), address)
     }
 
-    fn execute_jz(&mut self, address: u16) {
-        if self.get_flag(Flag::Z) {
-            self.set_program_counter(address);
-        }
+    fn execute_jnc(&mut self, address: u16) -> ProgramFlow {
+        self.jump_if(!self.get_flag(Flag::C), address)
     }
 
-    fn execute_jnz(&mut self, address: u16) {
-        if !self.get_flag(Flag::Z) {
-            self.set_program_counter(address);
-        }
+    fn execute_jz(&mut self, address: u16) -> ProgramFlow {
+        self.jump_if(self.get_flag(Flag::Z), address)
     }
 
-    fn execute_jm(&mut self, address: u16) {
-        if self.get_flag(Flag::S) {
-            self.set_program_counter(address);
-        }
+    fn execute_jnz(&mut self, address: u16) -> ProgramFlow {
+        self.jump_if(!self.get_flag(Flag::Z), address)
     }
 
-    fn execute_jp(&mut self, address: u16) {
-        if !self.get_flag(Flag::S) {
-            self.set_program_counter(address);
-        }
+    fn execute_jm(&mut self, address: u16) -> ProgramFlow {
+        self.jump_if(self.get_flag(Flag::S), address)
+    }
+
+    fn execute_jp(&mut self, address: u16) -> ProgramFlow {
+        self.jump_if(!self.get_flag(Flag::S), address)
+    }
+
+    fn execute_jpe(&mut self, address: u16) -> ProgramFlow {
+        self.jump_if(self.get_flag(Flag::P), address)
+    }
+
+    fn execute_jpo(&mut self, address: u16) -> ProgramFlow {
+        self.jump_if(!self.get_flag(Flag::P), address)
+    }
+
+    // pushes the address to return to and continues at address
+    fn call(&mut self, return_address: u16, address: u16) {
+        let stack_pointer = self.get_stack_pointer();
+        self.set_memory(stack_pointer.wrapping_sub(1), (return_address >> 8) as i8);
+        self.set_memory(stack_pointer.wrapping_sub(2), return_address as i8);
+        self.set_stack_pointer(stack_pointer.wrapping_sub(2));
+
+        self.set_program_counter(address);
     }
 
-    fn execute_jpe(&mut self, address: u16) {
-        if self.get_flag(Flag::P) {
-            self.set_program_counter(address);
+    // a call that isn't taken continues with the next instruction, CALL always is
+    fn call_if(&mut self, condition: bool, address: u16) -> ProgramFlow {
+        if !condition || self.handle_syscall(address) {
+            return ProgramFlow::Next;
         }
+
+        // returns right behind the 3 bytes of the call
+        self.call(self.get_program_counter().wrapping_add(3), address);
+        ProgramFlow::Jump
     }
 
-    fn execute_jpo(&mut self, address: u16) {
-        if !self.get_flag(Flag::P) {
-            self.set_program_counter(address);
+    fn return_if(&mut self, condition: bool) -> ProgramFlow {
+        if !condition {
+            return ProgramFlow::Next;
         }
+
+        self.execute_ret();
+        ProgramFlow::Jump
+    }
+
+    // single byte call of 8 * number, e.g. used by interrupts
+    fn execute_rst(&mut self, number: u8) -> ProgramFlow {
+        self.call(
+            self.get_program_counter().wrapping_add(1),
+            number as u16 * 8,
+        );
+        ProgramFlow::Jump
+    }
+
+    pub fn print_status(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        self.print_status_with(out, None)
     }
 
-    fn print_status(&self) {
+    // same as print_status with every value and address in the given radix
+    pub fn print_status_radix(&self, out: &mut dyn Write, radix: Radix) -> std::io::Result<()> {
+        self.print_status_with(out, Some(radix))
+    }
+
+    // without a radix registers are binary and decimal, everything else decimal
+    fn print_status_with(&self, out: &mut dyn Write, radix: Option<Radix>) -> std::io::Result<()> {
         for i in 0..7 {
-            println!(
-                "{}: {:#010b} ({})",
-                i,
-                self.get_register(InstructionRegister::from_index(i)),
-                self.get_register(InstructionRegister::from_index(i))
-            );
+            let register = InstructionRegister::from_index(i);
+            match radix {
+                Some(radix) => writeln!(
+                    out,
+                    "{}: {}",
+                    register,
+                    radix.format(self.get_register(register) as u8 as u16, 8)
+                )?,
+                None => writeln!(out, "{}", self.register_display(register))?,
+            }
         }
-        self.print_flags();
-        self.print_stack_pointer();
-        self.print_program_counter();
-        self.print_memory();
+        self.print_flags(out)?;
+        self.print_stack_pointer(out, radix)?;
+        self.print_program_counter(out, radix)?;
+        self.print_memory(out, radix)
+    }
+
+    // binary, signed and unsigned value, e.g. "B: 0b11111111 (-1 / 255)"
+    pub fn register_display(&self, register: InstructionRegister) -> String {
+        let value = self.get_register(register);
+
+        format!(
+            "{}: {:#010b} ({} / {})",
+            register, value, value, value as u8
+        )
     }
 
-    fn print_flags(&self) {
-        println!("Flags:");
+    fn print_flags(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(out, "Flags:")?;
         for flag in Flag::iter() {
-            println!("{:?}: {}", flag.clone(), self.get_flag(flag));
+            writeln!(out, "{:?}: {}", flag.clone(), self.get_flag(flag))?;
         }
+
+        Ok(())
     }
 
-    fn print_memory(&self) {
-        println!("Memory:");
+    fn print_memory(&self, out: &mut dyn Write, radix: Option<Radix>) -> std::io::Result<()> {
+        writeln!(out, "Memory:")?;
         for (address, value) in self.memory.iter().enumerate() {
-            if *value != 0 {
-                println!("{}: {}", address, value);
+            if *value == 0 {
+                continue;
             }
-        }
-    }
 
-    fn print_stack_pointer(&self) {
-        println!("Stack Pointer: {}", self.get_stack_pointer());
-    }
+            match radix {
+                Some(radix) => writeln!(
+                    out,
+                    "{}: {}",
+                    radix.format(address as u16, 16),
+                    radix.format(*value as u8 as u16, 8)
+                )?,
+                None => writeln!(out, "{}: {}", address, value)?,
+            }
+        }
 
-    fn print_program_counter(&self) {
-        println!("Program counter: {}", self.get_program_counter());
+        Ok(())
+    }
+
+    fn print_stack_pointer(
+        &self,
+        out: &mut dyn Write,
+        radix: Option<Radix>,
+    ) -> std::io::Result<()> {
+        let radix = radix.unwrap_or(Radix::Decimal);
+        writeln!(
+            out,
+            "Stack Pointer: {}",
+            radix.format(self.get_stack_pointer(), 16)
+        )
+    }
+
+    fn print_program_counter(
+        &self,
+        out: &mut dyn Write,
+        radix: Option<Radix>,
+    ) -> std::io::Result<()> {
+        let radix = radix.unwrap_or(Radix::Decimal);
+        writeln!(
+            out,
+            "Program counter: {}",
+            radix.format(self.get_program_counter(), 16)
+        )
     }
 }
 
 #[cfg(test)]
+// the flag tests compare against literal bools
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
-    use super::initialize_cpu;
-    use crate::assembler;
-    use crate::cpu::{Flag, InstructionRegister, InstructionRegisterPair};
+    use super::{initialize_cpu, pair_to_address, run_source, ProgramFlow};
+    use crate::assembler::{self, AssemblerError};
+    use crate::cpu::IoBus;
+    use crate::cpu::{
+        BreakReason, CpuBuilder, FillPattern, Flag, Flags, Instruction, InstructionCommand,
+        InstructionRegister, InstructionRegisterPair, Radix, RunOutcome, RunSummary, TraceRecord,
+    };
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
 
     #[test]
     fn test_execute_end_to_end() {
         let mut cpu = initialize_cpu();
 
-        let assembler =
+        let mut assembler =
             assembler::Assembler::new("data/test/end_to_end.asm".to_owned(), "output".to_owned());
 
-        assembler.assemble();
-        let instructions = assembler.disassemble("output".to_owned());
+        assembler.assemble().unwrap();
+        let instructions = assembler.disassemble().unwrap();
+        std::fs::remove_file("output").unwrap();
 
         cpu.run(instructions, false);
-
-        assert_eq!(cpu.get_register(InstructionRegister::A), -56);
-        assert_eq!(cpu.get_register(InstructionRegister::B), 27);
+        // XRA B leaves B alone and DAD B carries into the pushed flags
+        assert_eq!(cpu.get_register(InstructionRegister::A), -52);
+        assert_eq!(cpu.get_register(InstructionRegister::B), -1);
         assert_eq!(cpu.get_register(InstructionRegister::C), -1);
         assert_eq!(cpu.get_register(InstructionRegister::D), 0);
         assert_eq!(cpu.get_register(InstructionRegister::E), 0);
         assert_eq!(cpu.get_register(InstructionRegister::H), 0);
         assert_eq!(cpu.get_register(InstructionRegister::L), 0);
 
-        assert_eq!(cpu.get_flag(Flag::S), true);
-        assert_eq!(cpu.get_flag(Flag::Z), false);
-        assert_eq!(cpu.get_flag(Flag::A), true);
-        assert_eq!(cpu.get_flag(Flag::P), false);
-        assert_eq!(cpu.get_flag(Flag::C), true);
+        assert!(cpu.get_flag(Flag::S));
+        assert!(!cpu.get_flag(Flag::Z));
+        assert!(!cpu.get_flag(Flag::A));
+        assert!(cpu.get_flag(Flag::P));
+        assert!(!cpu.get_flag(Flag::C));
 
         assert_eq!(cpu.get_stack_pointer(), 12345);
-        assert_eq!(cpu.get_memory(7168), -124);
-        assert_eq!(cpu.get_memory(0), -28);
-        assert_eq!(cpu.get_memory(65535), 18);
-        assert_eq!(cpu.get_memory(42), 127);
-        assert_eq!(cpu.get_memory(12345), -1);
-        assert_eq!(cpu.get_memory(12346), 27);
+        assert_eq!(cpu.get_memory(0), 102);
+        assert_eq!(cpu.get_memory(65535), 23);
+        assert_eq!(cpu.get_memory(42), -1);
+        assert_eq!(cpu.get_memory(12345), 101);
+        assert_eq!(cpu.get_memory(12346), 0);
         assert_eq!(cpu.get_program_counter(), 92);
     }
 
     #[test]
-    fn test_execute_mvi() {
+    fn test_stop_on_halt() {
+        let instructions = assembler::assemble_str_indexed("MVI A,1\nHLT\nMVI B,2\nHLT\n").unwrap();
+
         let mut cpu = initialize_cpu();
+        let summary = cpu.run_program(&instructions);
+        assert_eq!(summary.halt_address, 2);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 0);
 
-        cpu.execute_mvi(&InstructionRegister::A, 14);
-        assert_eq!(cpu.get_register(InstructionRegister::A), 14);
+        // the first HLT is skipped, the last one still stops
+        let mut cpu = initialize_cpu();
+        cpu.set_stop_on_halt(false);
+        let summary = cpu.run_program(&instructions);
+        assert_eq!(
+            summary,
+            RunSummary {
+                instructions: 4,
+                halt_address: 5,
+            }
+        );
+        assert_eq!(cpu.get_register(InstructionRegister::A), 1);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 2);
+        assert!(cpu.is_halted());
+
+        let mut cpu = initialize_cpu();
+        cpu.set_stop_on_halt(false);
+        cpu.load_program(instructions);
+        assert_eq!(cpu.run_until_break(0), BreakReason::Halt);
+        assert_eq!(cpu.get_program_counter(), 6);
     }
 
     #[test]
-    fn test_execute_mov() {
+    fn test_run_program() {
         let mut cpu = initialize_cpu();
-        cpu.change_register(InstructionRegister::A, 10);
 
-        cpu.execute_mov(&(InstructionRegister::A, InstructionRegister::B));
-        assert_eq!(cpu.get_register(InstructionRegister::B), 10);
+        // B counts down from 3, DCR and JNZ run once per iteration
+        let mut instructions = vec![None; 65536];
+        instructions[0] = Some(Instruction::IntermediateRegister(
+            InstructionCommand::Mvi,
+            3,
+            InstructionRegister::B,
+        ));
+        instructions[2] = Some(Instruction::SingleRegister(
+            InstructionCommand::Dcr,
+            InstructionRegister::B,
+        ));
+        instructions[3] = Some(Instruction::Label(InstructionCommand::Jnz, 2));
+        instructions[6] = Some(Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let summary = cpu.run_program(&instructions);
+
+        assert_eq!(
+            summary,
+            RunSummary {
+                instructions: 8,
+                halt_address: 6,
+            }
+        );
+        assert_eq!(cpu.get_register(InstructionRegister::B), 0);
+        assert_eq!(cpu.get_program_counter(), 7);
     }
 
     #[test]
-    fn test_execute_add() {
+    fn test_is_halted() {
+        let mut instructions = HashMap::new();
+        instructions.insert(0, Instruction::NoRegister(InstructionCommand::Stc));
+        instructions.insert(1, Instruction::NoRegister(InstructionCommand::Hlt));
+
         let mut cpu = initialize_cpu();
-        cpu.change_register(InstructionRegister::A, 5);
-        cpu.set_flag(Flag::Z, true);
+        assert!(!cpu.is_halted());
 
-        cpu.execute_add(&InstructionRegister::A);
-        assert_eq!(cpu.get_register(InstructionRegister::A), 10);
-        assert_eq!(cpu.get_flag(Flag::Z), false);
+        cpu.run(instructions, false);
+        assert!(cpu.is_halted());
+        assert!(cpu.get_flag(Flag::C));
+        assert_eq!(cpu.get_program_counter(), 2);
+    }
 
-        cpu.change_register(InstructionRegister::A, -5);
-        cpu.execute_add(&InstructionRegister::A);
-        assert_eq!(cpu.get_register(InstructionRegister::A), -10);
-        assert_eq!(cpu.get_flag(Flag::S), true);
+    #[test]
+    fn test_run_program_from() {
+        let mut instructions = HashMap::new();
+        instructions.insert(0, Instruction::NoRegister(InstructionCommand::Hlt));
+        instructions.insert(
+            0x100,
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 5, InstructionRegister::A),
+        );
+        instructions.insert(0x102, Instruction::NoRegister(InstructionCommand::Hlt));
+        let instructions = assembler::index_by_address(instructions);
 
-        cpu.change_register(InstructionRegister::A, 127);
-        cpu.change_register(InstructionRegister::B, 127);
-        cpu.set_flag(Flag::C, true);
-        cpu.execute_add(&InstructionRegister::B);
-        assert_eq!(cpu.get_register(InstructionRegister::A), -2);
-        assert_eq!(cpu.get_flag(Flag::C), false);
+        let mut cpu = initialize_cpu();
+        let summary = cpu.run_program_from(&instructions, 0x100);
 
-        cpu.change_register(InstructionRegister::A, -64);
-        cpu.change_register(InstructionRegister::B, 64);
-        cpu.execute_add(&InstructionRegister::B);
-        assert_eq!(cpu.get_register(InstructionRegister::A), 0);
-        assert_eq!(cpu.get_flag(Flag::C), true);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 5);
+        assert_eq!(summary.instructions, 2);
+        assert_eq!(summary.halt_address, 0x102);
     }
 
     #[test]
-    fn test_execute_adc() {
+    fn test_register_pair() {
         let mut cpu = initialize_cpu();
-        cpu.change_register(InstructionRegister::A, 5);
-        cpu.set_flag(Flag::Z, true);
 
-        cpu.change_register(InstructionRegister::A, 10);
-        cpu.set_flag(Flag::C, false);
-        cpu.execute_adc(&InstructionRegister::A);
-        assert_eq!(cpu.get_register(InstructionRegister::A), 20);
+        cpu.set_register_pair(InstructionRegisterPair::BC, 0x1234);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 0x12);
+        assert_eq!(cpu.get_register(InstructionRegister::C), 0x34);
+        assert_eq!(cpu.register_pair(InstructionRegisterPair::BC), 0x1234);
 
-        cpu.change_register(InstructionRegister::A, 10);
-        cpu.set_flag(Flag::C, true);
-        cpu.execute_adc(&InstructionRegister::A);
-        assert_eq!(cpu.get_register(InstructionRegister::A), 21);
+        cpu.change_register(InstructionRegister::H, -1);
+        cpu.change_register(InstructionRegister::L, -2);
+        assert_eq!(cpu.register_pair(InstructionRegisterPair::HL), 0xfffe);
 
-        cpu.change_register(InstructionRegister::A, -64);
-        cpu.change_register(InstructionRegister::B, 63);
-        cpu.set_flag(Flag::C, true);
-        cpu.execute_adc(&InstructionRegister::B);
+        cpu.set_register_pair(InstructionRegisterPair::SP, 0x4321);
+        assert_eq!(cpu.get_stack_pointer(), 0x4321);
+
+        cpu.set_register_pair(InstructionRegisterPair::FA, 0x8081);
+        assert_eq!(cpu.get_register(InstructionRegister::A), -128);
+        assert!(cpu.get_flag(Flag::S));
+        assert!(cpu.get_flag(Flag::C));
+        assert_eq!(cpu.register_pair(InstructionRegisterPair::FA), 0x8083);
+    }
+
+    #[test]
+    fn test_pair_to_address() {
+        assert_eq!(pair_to_address(0x12, -1), 0x12ff);
+        assert_eq!(pair_to_address(-1, 0x12), 0xff12);
+        assert_eq!(pair_to_address(-128, -128), 0x8080);
+        assert_eq!(pair_to_address(0, 0), 0);
+    }
+
+    #[test]
+    fn test_run_source() {
+        let (cpu, summary) =
+            run_source("MVI A, 2\nloop: ADD A\nCPI 16\nJNZ loop\nHLT\nDB 0xFF\n").unwrap();
+        assert_eq!(cpu.get_register(InstructionRegister::A), 16);
+        assert!(cpu.is_halted());
+        // MVI, three rounds of the loop and HLT
+        assert_eq!(
+            summary,
+            RunSummary {
+                instructions: 11,
+                halt_address: 8
+            }
+        );
+        // the assembled bytes are in memory as well
+        assert_eq!(cpu.memory_range(0, 2), vec![0x3e, 2]);
+        assert_eq!(cpu.get_memory(9), -1);
+
+        assert!(run_source("MVI A, 0x100\nHLT").is_err());
+    }
+
+    #[test]
+    fn test_run_source_without_halt() {
+        assert!(matches!(
+            run_source("MVI A,1\n"),
+            Err(AssemblerError::InvalidInstruction(2, 0))
+        ));
+    }
+
+    #[test]
+    fn test_run_source_origin() {
+        let (cpu, summary) = run_source("ORG 0x100\nHLT\n").unwrap();
+
+        assert!(cpu.is_halted());
+        assert_eq!(summary.halt_address, 0x100);
+    }
+
+    #[test]
+    fn test_run_source_data() {
+        // DB 0x3C runs as INR A
+        let (cpu, _) = run_source("MVI A,1\nDB 0x3C\nHLT\n").unwrap();
+
+        assert_eq!(cpu.get_register(InstructionRegister::A), 2);
+    }
+
+    #[test]
+    fn test_subroutine() {
+        let (cpu, _) =
+            run_source(&std::fs::read_to_string("data/test/subroutine.asm").unwrap()).unwrap();
+
+        assert_eq!(cpu.get_register(InstructionRegister::A), 16);
+        // every taken call returned
+        assert_eq!(cpu.get_stack_pointer(), 0x0100);
+        assert_eq!(cpu.get_program_counter(), 23);
+    }
+
+    #[test]
+    fn test_restart() {
+        let source = "        JMP START
+        ORG 0x08
+        INR A
+        RET
+START:  LXI SP,0x0100
+        RST 1
+        RST 1
+        HLT
+";
+        let (cpu, _) = run_source(source).unwrap();
+
+        assert_eq!(cpu.get_register(InstructionRegister::A), 2);
+        assert_eq!(cpu.get_stack_pointer(), 0x0100);
+        // the return address of the second RST is still on the stack
+        assert_eq!(cpu.get_memory(0x00FE), 0x0F);
+    }
+
+    #[test]
+    fn test_conditional_return() {
+        let mut cpu = initialize_cpu();
+        cpu.set_stack_pointer(0x0FFE);
+        cpu.set_memory(0x0FFE, 0x34);
+        cpu.set_memory(0x0FFF, 0x12);
+
+        cpu.set_flag(Flag::Z, false);
+        assert_eq!(
+            cpu.execute(&Instruction::NoRegister(InstructionCommand::Rz)),
+            ProgramFlow::Next
+        );
+        assert_eq!(cpu.get_stack_pointer(), 0x0FFE);
+
+        assert_eq!(
+            cpu.execute(&Instruction::NoRegister(InstructionCommand::Rnz)),
+            ProgramFlow::Jump
+        );
+        assert_eq!(cpu.get_program_counter(), 0x1234);
+        assert_eq!(cpu.get_stack_pointer(), 0x1000);
+    }
+
+    #[test]
+    fn test_memory_register() {
+        let mut cpu = initialize_cpu();
+
+        let mut assembler = assembler::Assembler::new(
+            "data/test/memory_register.asm".to_owned(),
+            "test_memory_register_binary".to_owned(),
+        );
+        assembler.assemble().unwrap();
+        let instructions = assembler.disassemble_indexed().unwrap();
+        std::fs::remove_file("test_memory_register_binary").unwrap();
+
+        cpu.run_program(&instructions);
+
+        assert_eq!(cpu.get_memory(0x1000), 0x43);
+        assert_eq!(cpu.get_memory(0x1001), 0x07);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 0x43);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x07);
+        assert_eq!(cpu.get_register(InstructionRegister::L), 0x01);
+    }
+
+    #[test]
+    fn test_run_indexed() {
+        let mut instructions = HashMap::new();
+        let mut address: u16 = 0;
+        for i in 0..3000 {
+            let instruction = match i % 4 {
+                0 => Instruction::IntermediateRegister(
+                    InstructionCommand::Mvi,
+                    i as i8,
+                    InstructionRegister::B,
+                ),
+                1 => Instruction::SingleRegister(InstructionCommand::Add, InstructionRegister::B),
+                2 => Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 0x4000 + i),
+                _ => {
+                    Instruction::PairRegister(InstructionCommand::Inx, InstructionRegisterPair::HL)
+                }
+            };
+
+            let size = instruction.get_size();
+            instructions.insert(address, instruction);
+            address += size;
+        }
+        instructions.insert(address, Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let indexed = assembler::index_by_address(instructions);
+        let mut cpu = initialize_cpu();
+        cpu.run_indexed(&indexed, false);
+
+        // every group of four adds the value just moved to B and stores the sum
+        let mut acc: u8 = 0;
+        for i in (0..3000u16).step_by(4) {
+            acc = acc.wrapping_add(i as i8 as u8);
+            assert_eq!(cpu.get_memory(0x4000 + i + 2), acc as i8);
+        }
+        assert_eq!(cpu.get_register(InstructionRegister::A), acc as i8);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 2996u16 as i8);
+        assert_eq!(cpu.get_register(InstructionRegister::H), 2);
+        assert_eq!(cpu.get_register(InstructionRegister::L), -18);
+        assert_eq!(cpu.get_program_counter(), address + 1);
+    }
+
+    #[test]
+    fn test_execute_mvi() {
+        let mut cpu = initialize_cpu();
+
+        cpu.execute_mvi(&InstructionRegister::A, 14);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 14);
+    }
+
+    #[test]
+    fn test_execute_mov() {
+        let mut cpu = initialize_cpu();
+        cpu.change_register(InstructionRegister::A, 10);
+
+        cpu.execute_mov(&(InstructionRegister::B, InstructionRegister::A));
+        assert_eq!(cpu.get_register(InstructionRegister::B), 10);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 10);
+
+        cpu.change_register(InstructionRegister::H, 0x20);
+        cpu.change_register(InstructionRegister::L, 0x01);
+        cpu.execute_mov(&(InstructionRegister::M, InstructionRegister::B));
+        assert_eq!(cpu.get_memory(0x2001), 10);
+
+        cpu.set_memory(0x2001, -3);
+        cpu.execute_mov(&(InstructionRegister::C, InstructionRegister::M));
+        assert_eq!(cpu.get_register(InstructionRegister::C), -3);
+    }
+
+    #[test]
+    fn test_execute_add() {
+        let mut cpu = initialize_cpu();
+        cpu.change_register(InstructionRegister::A, 5);
+        cpu.set_flag(Flag::Z, true);
+
+        cpu.execute_add(&InstructionRegister::A);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 10);
+        assert_eq!(cpu.get_flag(Flag::Z), false);
+
+        cpu.change_register(InstructionRegister::A, -5);
+        cpu.execute_add(&InstructionRegister::A);
+        assert_eq!(cpu.get_register(InstructionRegister::A), -10);
+        assert_eq!(cpu.get_flag(Flag::S), true);
+
+        cpu.change_register(InstructionRegister::A, 127);
+        cpu.change_register(InstructionRegister::B, 127);
+        cpu.set_flag(Flag::C, true);
+        cpu.execute_add(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), -2);
+        assert_eq!(cpu.get_flag(Flag::C), false);
+
+        cpu.change_register(InstructionRegister::A, -64);
+        cpu.change_register(InstructionRegister::B, 64);
+        cpu.execute_add(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0);
+        assert_eq!(cpu.get_flag(Flag::C), true);
+        assert!(!cpu.get_flag(Flag::A));
+
+        cpu.change_register(InstructionRegister::A, 0x0f);
+        cpu.change_register(InstructionRegister::B, 0x01);
+        cpu.execute_add(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x10);
+        assert!(cpu.get_flag(Flag::A));
+        assert!(!cpu.get_flag(Flag::P));
+        assert!(!cpu.get_flag(Flag::C));
+    }
+
+    #[test]
+    fn test_execute_adc() {
+        let mut cpu = initialize_cpu();
+        cpu.change_register(InstructionRegister::A, 5);
+        cpu.set_flag(Flag::Z, true);
+
+        cpu.change_register(InstructionRegister::A, 10);
+        cpu.set_flag(Flag::C, false);
+        cpu.execute_adc(&InstructionRegister::A);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 20);
+
+        cpu.change_register(InstructionRegister::A, 10);
+        cpu.set_flag(Flag::C, true);
+        cpu.execute_adc(&InstructionRegister::A);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 21);
+
+        cpu.change_register(InstructionRegister::A, -64);
+        cpu.change_register(InstructionRegister::B, 63);
+        cpu.set_flag(Flag::C, true);
+        cpu.execute_adc(&InstructionRegister::B);
         assert_eq!(cpu.get_register(InstructionRegister::A), 0);
         assert_eq!(cpu.get_flag(Flag::C), true);
 
@@ -1306,6 +2240,96 @@ mod tests {
         assert_eq!(cpu.get_flag(Flag::C), false);
     }
 
+    #[test]
+    fn test_adc_wraps() {
+        let mut cpu = initialize_cpu();
+
+        // 0x7F + 0x00 + carry crosses into the negative range
+        cpu.change_register(InstructionRegister::A, 0x7f);
+        cpu.change_register(InstructionRegister::B, 0);
+        cpu.set_flag(Flag::C, true);
+        cpu.execute_adc(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), -128);
+        assert!(cpu.get_flag(Flag::S));
+        assert!(!cpu.get_flag(Flag::C));
+
+        // 0xFF + 0x00 + carry wraps to 0
+        cpu.change_register(InstructionRegister::A, -1);
+        cpu.set_flag(Flag::C, true);
+        cpu.execute_adc(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0);
+        assert!(cpu.get_flag(Flag::Z));
+        assert!(cpu.get_flag(Flag::C));
+
+        // 0x80 + 0x80 + carry
+        cpu.change_register(InstructionRegister::A, -128);
+        cpu.change_register(InstructionRegister::B, -128);
+        cpu.set_flag(Flag::C, true);
+        cpu.execute_adc(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 1);
+        assert!(cpu.get_flag(Flag::C));
+    }
+
+    #[test]
+    fn test_inr_dcr_wrap() {
+        let mut cpu = initialize_cpu();
+
+        cpu.change_register(InstructionRegister::B, 0x7f);
+        cpu.execute_inr(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::B), -128);
+
+        cpu.change_register(InstructionRegister::B, -1);
+        cpu.execute_inr(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 0);
+        assert!(cpu.get_flag(Flag::Z));
+
+        cpu.execute_dcr(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::B), -1);
+
+        cpu.change_register(InstructionRegister::B, -128);
+        cpu.execute_dcr(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 0x7f);
+        assert!(!cpu.get_flag(Flag::S));
+    }
+
+    #[test]
+    fn test_16_bit_addresses_wrap() {
+        let mut cpu = initialize_cpu();
+
+        cpu.change_register(InstructionRegister::H, 0x12);
+        cpu.change_register(InstructionRegister::L, 0x34);
+        cpu.execute_shld(0xffff_u16 as i16);
+        assert_eq!(cpu.get_memory(0xffff), 0x34);
+        assert_eq!(cpu.get_memory(0), 0x12);
+
+        cpu.set_memory(0xffff, 0x56);
+        cpu.set_memory(0, 0x78);
+        cpu.execute_lhld(0xffff_u16 as i16);
+        assert_eq!(cpu.get_register(InstructionRegister::L), 0x56);
+        assert_eq!(cpu.get_register(InstructionRegister::H), 0x78);
+
+        cpu.set_stack_pointer(0xffff);
+        cpu.change_register(InstructionRegister::H, 0x12);
+        cpu.change_register(InstructionRegister::L, 0x34);
+        cpu.execute_xthl();
+        assert_eq!(cpu.get_register(InstructionRegister::L), 0x56);
+        assert_eq!(cpu.get_register(InstructionRegister::H), 0x78);
+        assert_eq!(cpu.get_memory(0xffff), 0x34);
+        assert_eq!(cpu.get_memory(0), 0x12);
+    }
+
+    #[test]
+    fn test_program_counter_wraps() {
+        let mut instructions = HashMap::new();
+        instructions.insert(0xffff, Instruction::NoRegister(InstructionCommand::Nop));
+        instructions.insert(0, Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let mut cpu = initialize_cpu();
+        let summary = cpu.run_program_from(&assembler::index_by_address(instructions), 0xffff);
+        assert_eq!(summary.halt_address, 0);
+        assert_eq!(summary.instructions, 2);
+    }
+
     #[test]
     fn test_execute_adi() {
         let mut cpu = initialize_cpu();
@@ -1331,6 +2355,15 @@ mod tests {
         cpu.execute_adi(127);
         assert_eq!(cpu.get_register(InstructionRegister::A), -2);
         assert_eq!(cpu.get_flag(Flag::C), false);
+
+        // flags come from the result, not the old accumulator
+        cpu.change_register(InstructionRegister::A, 0x01);
+        cpu.execute_adi(0xff_u8 as i8);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0);
+        assert!(cpu.get_flag(Flag::Z));
+        assert!(!cpu.get_flag(Flag::S));
+        assert!(cpu.get_flag(Flag::P));
+        assert!(cpu.get_flag(Flag::C));
     }
 
     #[test]
@@ -1443,6 +2476,68 @@ mod tests {
         cpu.execute_sub(&InstructionRegister::B);
         assert_eq!(cpu.get_register(InstructionRegister::A), 27);
         assert_eq!(cpu.get_flag(Flag::C), true);
+
+        cpu.change_register(InstructionRegister::A, 0);
+        cpu.change_register(InstructionRegister::B, 1);
+        cpu.execute_sub(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), -1);
+        assert!(cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::S));
+    }
+
+    #[test]
+    fn test_execute_sui() {
+        let mut cpu = initialize_cpu();
+
+        cpu.execute_sui(1);
+        assert_eq!(cpu.get_register(InstructionRegister::A), -1);
+        assert!(cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::S));
+
+        cpu.change_register(InstructionRegister::A, -1);
+        cpu.execute_sui(-1);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0);
+        assert!(!cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::Z));
+
+        cpu.change_register(InstructionRegister::A, 5);
+        cpu.execute_sui(3);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 2);
+        assert!(!cpu.get_flag(Flag::C));
+    }
+
+    #[test]
+    fn test_execute_sub_auxiliary_carry() {
+        let mut cpu = initialize_cpu();
+
+        // no borrow from bit 4, the complement add carries out of bit 3
+        cpu.change_register(InstructionRegister::A, 0x15);
+        cpu.change_register(InstructionRegister::B, 0x01);
+        cpu.execute_sub(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x14);
+        assert!(cpu.get_flag(Flag::A));
+
+        // DAA sees the auxiliary carry and corrects the low nibble
+        cpu.execute_daa();
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x1A);
+
+        cpu.change_register(InstructionRegister::A, 0x10);
+        cpu.execute_sub(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x0F);
+        assert!(!cpu.get_flag(Flag::A));
+
+        cpu.change_register(InstructionRegister::A, 0x15);
+        cpu.execute_sui(0x01);
+        assert!(cpu.get_flag(Flag::A));
+
+        cpu.change_register(InstructionRegister::A, 0x10);
+        cpu.execute_sui(0x01);
+        assert!(!cpu.get_flag(Flag::A));
+
+        // subtracting 0 carries like compare does
+        cpu.change_register(InstructionRegister::A, 0);
+        cpu.execute_sui(0);
+        assert!(cpu.get_flag(Flag::A));
     }
 
     #[test]
@@ -1458,6 +2553,71 @@ mod tests {
         assert_eq!(cpu.get_flag(Flag::S), true);
     }
 
+    #[test]
+    fn test_execute_inr_flags_follow_register() {
+        let mut cpu = initialize_cpu();
+        cpu.change_register(InstructionRegister::A, 5);
+        cpu.change_register(InstructionRegister::B, 127);
+
+        // the flags describe B, not the accumulator
+        cpu.execute_inr(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::B), -128);
+        assert!(cpu.get_flag(Flag::S));
+        assert!(!cpu.get_flag(Flag::Z));
+
+        cpu.change_register(InstructionRegister::C, 1);
+        cpu.execute_dcr(&InstructionRegister::C);
+        assert!(cpu.get_flag(Flag::Z));
+        assert!(cpu.get_flag(Flag::P));
+    }
+
+    #[test]
+    fn test_execute_inr_dcr_auxiliary_carry() {
+        let mut cpu = initialize_cpu();
+
+        cpu.change_register(InstructionRegister::B, 0x0f);
+        cpu.execute_inr(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 0x10);
+        assert!(cpu.get_flag(Flag::A));
+
+        cpu.execute_inr(&InstructionRegister::B);
+        assert!(!cpu.get_flag(Flag::A));
+
+        cpu.change_register(InstructionRegister::B, 0x10);
+        cpu.execute_dcr(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 0x0f);
+        assert!(!cpu.get_flag(Flag::A));
+
+        cpu.execute_dcr(&InstructionRegister::B);
+        assert!(cpu.get_flag(Flag::A));
+
+        // the carry flag isn't touched
+        cpu.set_flag(Flag::C, true);
+        cpu.change_register(InstructionRegister::B, -1);
+        cpu.execute_inr(&InstructionRegister::B);
+        assert!(cpu.get_flag(Flag::C));
+    }
+
+    #[test]
+    fn test_update_szp() {
+        let mut cpu = initialize_cpu();
+
+        cpu.update_szp(0x00);
+        assert!(!cpu.get_flag(Flag::S));
+        assert!(cpu.get_flag(Flag::Z));
+        assert!(cpu.get_flag(Flag::P));
+
+        cpu.update_szp(0x80u8 as i8);
+        assert!(cpu.get_flag(Flag::S));
+        assert!(!cpu.get_flag(Flag::Z));
+        assert!(!cpu.get_flag(Flag::P));
+
+        cpu.update_szp(0x7F);
+        assert!(!cpu.get_flag(Flag::S));
+        assert!(!cpu.get_flag(Flag::Z));
+        assert!(!cpu.get_flag(Flag::P));
+    }
+
     #[test]
     fn test_execute_dcr() {
         let mut cpu = initialize_cpu();
@@ -1488,8 +2648,11 @@ mod tests {
         // ANA 11110000
 
         cpu.change_register(InstructionRegister::A, -15);
+        cpu.set_flag(Flag::C, true);
         cpu.execute_ana(&InstructionRegister::B);
         assert_eq!(cpu.get_register(InstructionRegister::A), -16);
+        assert_eq!(cpu.get_flag(Flag::C), false);
+        assert_eq!(cpu.get_flag(Flag::A), false);
     }
 
     #[test]
@@ -1678,6 +2841,41 @@ mod tests {
         assert_eq!(cpu.get_register(InstructionRegister::A), 1);
         assert_eq!(cpu.get_flag(Flag::C), true);
         assert_eq!(cpu.get_flag(Flag::A), true);
+
+        // 0x9A, low nibble correction carries into the high nibble
+        cpu.set_flag(Flag::A, false);
+        cpu.set_flag(Flag::C, false);
+        cpu.change_register(InstructionRegister::A, 0x9Au8 as i8);
+        cpu.execute_daa();
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0);
+        assert!(cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::A));
+
+        // auxiliary carry from a previous ADD, low nibble itself is valid BCD
+        // 0x19 + 0x19 = 0x32 -> 0x38
+        cpu.set_flag(Flag::A, true);
+        cpu.set_flag(Flag::C, false);
+        cpu.change_register(InstructionRegister::A, 0x32);
+        cpu.execute_daa();
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x38);
+        assert!(!cpu.get_flag(Flag::A));
+        assert!(!cpu.get_flag(Flag::C));
+
+        // a carry from the previous addition stays set, 0x99 + 0x99 = 0x132
+        cpu.set_flag(Flag::A, true);
+        cpu.set_flag(Flag::C, true);
+        cpu.change_register(InstructionRegister::A, 0x32);
+        cpu.execute_daa();
+        assert_eq!(cpu.get_register(InstructionRegister::A), -104);
+        assert!(cpu.get_flag(Flag::C));
+
+        // 0xFA is corrected in both nibbles, not only the low one
+        cpu.set_flag(Flag::A, false);
+        cpu.set_flag(Flag::C, false);
+        cpu.change_register(InstructionRegister::A, 0xFAu8 as i8);
+        cpu.execute_daa();
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x60);
+        assert!(cpu.get_flag(Flag::C));
     }
 
     #[test]
@@ -1707,12 +2905,13 @@ mod tests {
     fn test_execute_cmp() {
         let mut cpu = initialize_cpu();
 
-        cpu.set_flag(Flag::C, true);
+        // 0x0A < 0xFB
+        cpu.set_flag(Flag::C, false);
         cpu.set_flag(Flag::Z, true);
         cpu.change_register(InstructionRegister::A, 10);
         cpu.change_register(InstructionRegister::E, -5);
         cpu.execute_cmp(&InstructionRegister::E);
-        assert_eq!(cpu.get_flag(Flag::C), false);
+        assert!(cpu.get_flag(Flag::C));
         assert_eq!(cpu.get_flag(Flag::Z), false);
 
         cpu.set_flag(Flag::C, false);
@@ -1723,13 +2922,43 @@ mod tests {
         assert_eq!(cpu.get_flag(Flag::C), true);
         assert_eq!(cpu.get_flag(Flag::Z), false);
 
-        cpu.set_flag(Flag::C, true);
+        // 0xE5 < 0xFB
+        cpu.set_flag(Flag::C, false);
         cpu.set_flag(Flag::Z, true);
         cpu.change_register(InstructionRegister::A, -27);
         cpu.change_register(InstructionRegister::E, -5);
         cpu.execute_cmp(&InstructionRegister::E);
+        assert!(cpu.get_flag(Flag::C));
+        assert!(!cpu.get_flag(Flag::Z));
+        assert!(cpu.get_flag(Flag::S));
+
+        // 0xFB > 0x0A
+        cpu.change_register(InstructionRegister::A, -5);
+        cpu.change_register(InstructionRegister::E, 10);
+        cpu.execute_cmp(&InstructionRegister::E);
         assert_eq!(cpu.get_flag(Flag::C), false);
         assert_eq!(cpu.get_flag(Flag::Z), false);
+
+        cpu.set_flag(Flag::C, true);
+        cpu.change_register(InstructionRegister::A, 0x10);
+        cpu.change_register(InstructionRegister::E, 0x10);
+        cpu.execute_cmp(&InstructionRegister::E);
+        assert!(!cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::Z));
+        assert!(cpu.get_flag(Flag::P));
+        assert!(!cpu.get_flag(Flag::S));
+        assert!(cpu.get_flag(Flag::A));
+
+        // equal negative numbers
+        cpu.set_flag(Flag::C, true);
+        cpu.change_register(InstructionRegister::A, -5);
+        cpu.change_register(InstructionRegister::E, -5);
+        cpu.execute_cmp(&InstructionRegister::E);
+        assert!(!cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::Z));
+
+        // the accumulator is not changed
+        assert_eq!(cpu.get_register(InstructionRegister::A), -5);
     }
 
     #[test]
@@ -1745,9 +2974,12 @@ mod tests {
         cpu.set_flag(Flag::Z, true);
         cpu.change_register(InstructionRegister::A, 92);
         cpu.change_register(InstructionRegister::B, 120);
+        cpu.set_flag(Flag::C, true);
         cpu.execute_xra(&InstructionRegister::B);
-        assert_eq!(cpu.get_register(InstructionRegister::B), 36);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 36);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 120);
         assert_eq!(cpu.get_flag(Flag::Z), false);
+        assert_eq!(cpu.get_flag(Flag::C), false);
     }
 
     #[test]
@@ -1764,6 +2996,41 @@ mod tests {
         assert_eq!(cpu.get_flag(Flag::C), false);
     }
 
+    #[test]
+    fn test_execute_sbb_borrow() {
+        let mut cpu = initialize_cpu();
+
+        // 0x00 - 0x80 - 1 borrows and leaves 0x7F
+        cpu.set_flag(Flag::C, true);
+        cpu.change_register(InstructionRegister::A, 0);
+        cpu.change_register(InstructionRegister::B, -128);
+        cpu.execute_sbb(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 127);
+        assert!(cpu.get_flag(Flag::C));
+        assert!(!cpu.get_flag(Flag::S));
+        assert!(!cpu.get_flag(Flag::Z));
+        assert!(!cpu.get_flag(Flag::P));
+
+        // the carry alone is enough to borrow
+        cpu.set_flag(Flag::C, true);
+        cpu.change_register(InstructionRegister::A, 5);
+        cpu.change_register(InstructionRegister::B, 5);
+        cpu.execute_sbb(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), -1);
+        assert!(cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::S));
+        assert!(cpu.get_flag(Flag::P));
+        assert!(!cpu.get_flag(Flag::A));
+
+        cpu.set_flag(Flag::C, false);
+        cpu.change_register(InstructionRegister::A, 5);
+        cpu.execute_sbb(&InstructionRegister::B);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0);
+        assert!(!cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::Z));
+        assert!(cpu.get_flag(Flag::A));
+    }
+
     #[test]
     fn test_execute_xchg() {
         let mut cpu = initialize_cpu();
@@ -1806,6 +3073,17 @@ mod tests {
         assert_eq!(cpu.get_register(InstructionRegister::L), -16);
         assert_eq!(cpu.get_memory(4269), 60);
         assert_eq!(cpu.get_memory(4270), 11);
+
+        // the high byte wraps around to address 0
+        cpu.set_stack_pointer(0xFFFF);
+        cpu.set_memory(0xFFFF, 1);
+        cpu.set_memory(0, 2);
+        cpu.execute_xthl();
+
+        assert_eq!(cpu.get_register(InstructionRegister::L), 1);
+        assert_eq!(cpu.get_register(InstructionRegister::H), 2);
+        assert_eq!(cpu.get_memory(0xFFFF), -16);
+        assert_eq!(cpu.get_memory(0), 13);
     }
 
     #[test]
@@ -1843,6 +3121,48 @@ mod tests {
         cpu.set_stack_pointer(65535);
         cpu.execute_inx(&InstructionRegisterPair::SP);
         assert_eq!(cpu.get_stack_pointer(), 0);
+
+        // a negative high register must not leak into the low one
+        cpu.change_register(InstructionRegister::B, -1);
+        cpu.change_register(InstructionRegister::C, 0);
+        cpu.execute_inx(&InstructionRegisterPair::BC);
+        assert_eq!(cpu.get_register(InstructionRegister::B), -1);
+        assert_eq!(cpu.get_register(InstructionRegister::C), 1);
+
+        cpu.change_register(InstructionRegister::C, -1);
+        cpu.execute_inx(&InstructionRegisterPair::BC);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 0);
+        assert_eq!(cpu.get_register(InstructionRegister::C), 0);
+    }
+
+    // DAD only changes the carry, even when the result is 0 or negative
+    #[test]
+    fn test_execute_dad_keeps_other_flags() {
+        let mut cpu = initialize_cpu();
+
+        // 0xffff + 1 carries and leaves 0 in HL
+        cpu.set_flags_from_mask(0b1_1110);
+        cpu.set_register_pair(InstructionRegisterPair::HL, 0xffff);
+        cpu.set_register_pair(InstructionRegisterPair::DE, 1);
+        cpu.execute_dad(&InstructionRegisterPair::DE);
+        assert_eq!(cpu.hl_address(), 0);
+        assert_eq!(cpu.flags_as_mask(), 0b1_1111);
+
+        cpu.set_flags_from_mask(0);
+        cpu.execute_dad(&InstructionRegisterPair::DE);
+        assert_eq!(cpu.hl_address(), 1);
+        assert_eq!(cpu.flags_as_mask(), 0);
+
+        // no carry, the result has the sign bit set and odd parity
+        cpu.set_flags_from_mask(0b1_1111);
+        cpu.set_register_pair(InstructionRegisterPair::HL, 0x7fff);
+        cpu.execute_dad(&InstructionRegisterPair::DE);
+        assert_eq!(cpu.hl_address(), 0x8000);
+        assert_eq!(cpu.flags_as_mask(), 0b1_1110);
+
+        cpu.set_flags_from_mask(0b0_1010);
+        cpu.execute_dad(&InstructionRegisterPair::DE);
+        assert_eq!(cpu.flags_as_mask(), 0b0_1010);
     }
 
     #[test]
@@ -1898,6 +3218,217 @@ mod tests {
         assert_eq!(cpu.get_stack_pointer(), 20520);
     }
 
+    #[test]
+    fn test_rom_region() {
+        let mut instructions = HashMap::new();
+        instructions.insert(
+            0,
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 5, InstructionRegister::A),
+        );
+        instructions.insert(
+            2,
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 0x42),
+        );
+        instructions.insert(
+            5,
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 0x100),
+        );
+        instructions.insert(8, Instruction::NoRegister(InstructionCommand::Hlt));
+        let instructions = assembler::index_by_address(instructions);
+
+        let mut cpu = initialize_cpu();
+        cpu.set_memory(0x42, 1);
+        cpu.set_rom_region(0, 0xff);
+        cpu.run_program(&instructions);
+
+        assert_eq!(cpu.get_memory(0x42), 1);
+        assert_eq!(cpu.get_memory(0x100), 5);
+
+        cpu.clear_rom_regions();
+        cpu.run_program_from(&instructions, 0);
+        assert_eq!(cpu.get_memory(0x42), 5);
+    }
+
+    #[test]
+    fn test_syscall_handler() {
+        let source = "        JMP START
+        ORG 5
+BDOS:   HLT ; replaced by the syscall handler
+START:  LXI SP,0x1000
+        MVI C,9
+        LXI D,MSG
+        CALL BDOS
+        CNZ BDOS ; Z is clear, called again
+        CZ BDOS
+DONE:   HLT
+MSG:    DB 'Hi!$'
+";
+        let bytes = assembler::assemble_str(source).unwrap();
+        let instructions = assembler::assemble_str_indexed(source).unwrap();
+
+        let mut cpu = initialize_cpu();
+        for (address, byte) in bytes.iter().enumerate() {
+            cpu.set_memory(address as u16, *byte as i8);
+        }
+
+        // BDOS function 9 prints the string at DE up to $
+        let output = Rc::new(RefCell::new(String::new()));
+        let captured = output.clone();
+        cpu.set_syscall_handler(
+            0x0005,
+            Box::new(move |function, address, memory| {
+                assert_eq!(function, 9);
+                let text = memory[address as usize..]
+                    .iter()
+                    .take_while(|byte| **byte as u8 != b'$')
+                    .map(|byte| *byte as u8 as char);
+                captured.borrow_mut().extend(text);
+            }),
+        );
+
+        let summary = cpu.run_program(&instructions);
+        assert_eq!(*output.borrow(), "Hi!Hi!");
+        assert_eq!(cpu.get_stack_pointer(), 0x1000);
+        assert_eq!(
+            Some(&Instruction::NoRegister(InstructionCommand::Hlt)),
+            instructions[summary.halt_address as usize].as_ref()
+        );
+        assert_ne!(summary.halt_address, 5);
+
+        // jumps aren't trapped, only calls
+        cpu.set_program_counter(5);
+        assert_eq!(cpu.run_program(&instructions).halt_address, 5);
+        assert_eq!(*output.borrow(), "Hi!Hi!");
+
+        // without a handler the code at 0x0005 runs again
+        cpu.remove_syscall_handler(0x0005);
+        cpu.set_program_counter(0);
+        assert_eq!(cpu.run_program(&instructions).halt_address, 5);
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        let source = "        MVI A,0x3C ; opcode of INR A
+        STA PATCH
+PATCH:  NOP ; INR A once the STA ran
+        HLT
+";
+        let bytes = assembler::assemble_str(source).unwrap();
+
+        let mut cpu = initialize_cpu();
+        for (address, byte) in bytes.iter().enumerate() {
+            cpu.set_memory(address as u16, *byte as i8);
+        }
+        cpu.load_program(assembler::assemble_str_indexed(source).unwrap());
+
+        assert_eq!(cpu.run_until_break(0), BreakReason::Halt);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x3D);
+        assert_eq!(
+            cpu.program[5],
+            Some(Instruction::SingleRegister(
+                InstructionCommand::Inr,
+                InstructionRegister::A
+            ))
+        );
+    }
+
+    #[test]
+    fn test_self_modifying_code_run_source() {
+        let (cpu, _) = run_source(
+            "        MVI A,0x3C ; opcode of INR A
+        STA PATCH
+PATCH:  NOP ; INR A once the STA ran
+        HLT
+",
+        )
+        .unwrap();
+
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x3D);
+    }
+
+    #[test]
+    fn test_execute_data() {
+        let source = "        MVI A,0x3C ; opcode of INR A
+        STA BUF
+BUF:    DB 0 ; INR A once the STA ran
+        DB 0x3C ; INR A
+        HLT
+";
+        let bytes = assembler::assemble_str(source).unwrap();
+
+        let mut cpu = initialize_cpu();
+        for (address, byte) in bytes.iter().enumerate() {
+            cpu.set_memory(address as u16, *byte as i8);
+        }
+        cpu.load_program(assembler::assemble_str_indexed(source).unwrap());
+
+        assert_eq!(cpu.run_until_break(0), BreakReason::Halt);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x3E);
+    }
+
+    #[test]
+    fn test_self_modifying_code_in_data() {
+        let source = "        MVI A,0x3C ; opcode of INR A
+        STA BUF+1
+        JMP BUF+1
+BUF:    DB 0, 0 ; INR A in the second byte once the STA ran
+        HLT
+";
+        let bytes = assembler::assemble_str(source).unwrap();
+
+        let mut cpu = initialize_cpu();
+        for (address, byte) in bytes.iter().enumerate() {
+            cpu.set_memory(address as u16, *byte as i8);
+        }
+        cpu.load_program(assembler::assemble_str_indexed(source).unwrap());
+
+        assert_eq!(cpu.run_until_break(0), BreakReason::Halt);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0x3D);
+    }
+
+    #[test]
+    fn test_restore_decodes_changed_code() {
+        let source = "        INR A
+        HLT
+";
+        let mut cpu = initialize_cpu();
+        cpu.load_program(assembler::assemble_str_indexed(source).unwrap());
+        let mut state = cpu.snapshot();
+        // DCR A instead of INR A
+        state.memory = vec![0x3D, 0x76];
+        cpu.restore(&state);
+
+        assert_eq!(cpu.run_until_break(0), BreakReason::Halt);
+        assert_eq!(cpu.get_register(InstructionRegister::A), -1);
+    }
+
+    #[test]
+    fn test_stack_guard() {
+        let mut cpu = initialize_cpu();
+        cpu.set_stack_pointer(0x2000);
+
+        let violations = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&violations);
+        cpu.set_stack_guard(
+            0x1FFE,
+            0x2000,
+            Box::new(move |stack_pointer| recorded.borrow_mut().push(stack_pointer)),
+        );
+
+        cpu.execute_push(&InstructionRegisterPair::BC);
+        assert!(violations.borrow().is_empty());
+
+        cpu.execute_push(&InstructionRegisterPair::BC);
+        assert_eq!(*violations.borrow(), vec![0x1FFC]);
+
+        // the push still happens, the guard only reports it
+        assert_eq!(cpu.get_stack_pointer(), 0x1FFC);
+
+        cpu.clear_stack_guard();
+        cpu.execute_push(&InstructionRegisterPair::BC);
+        assert_eq!(violations.borrow().len(), 1);
+    }
+
     #[test]
     fn test_execute_pop() {
         let mut cpu = initialize_cpu();
@@ -1925,6 +3456,98 @@ mod tests {
         assert_eq!(cpu.get_stack_pointer(), 11266);
     }
 
+    #[test]
+    fn test_memory_range() {
+        let mut cpu = initialize_cpu();
+
+        for (offset, value) in [1, -2, 3].iter().enumerate() {
+            cpu.change_register(InstructionRegister::A, *value);
+            cpu.execute_sta(0x2000 + offset as i16);
+        }
+
+        assert_eq!(cpu.memory_range(0x2000, 3), vec![1, -2, 3]);
+        assert_eq!(cpu.memory_range(0x1FFF, 5), vec![0, 1, -2, 3, 0]);
+        assert!(cpu.memory_range(0x2000, 0).is_empty());
+
+        cpu.set_memory(0xFFFF, 7);
+        cpu.set_memory(0x0000, 8);
+        assert_eq!(cpu.memory_range(0xFFFF, 2), vec![7, 8]);
+    }
+
+    #[test]
+    fn test_flags_byte() {
+        let mut cpu = initialize_cpu();
+        assert_eq!(cpu.flags_byte(), 0b0000_0010);
+
+        cpu.set_flags_byte(0b1101_0111);
+        assert!(cpu.get_flag(Flag::S));
+        assert!(cpu.get_flag(Flag::Z));
+        assert!(cpu.get_flag(Flag::A));
+        assert!(cpu.get_flag(Flag::P));
+        assert!(cpu.get_flag(Flag::C));
+
+        assert_eq!(cpu.flags_byte(), 0b1101_0111);
+
+        // the unused bits always read back as 0 and bit 1 as 1
+        cpu.set_flags_byte(0b0010_1000);
+        assert_eq!(cpu.flags_byte(), 0b0000_0010);
+
+        cpu.set_flags_byte(0b0100_0101);
+        assert_eq!(cpu.flags_byte(), 0b0100_0111);
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut cpu = initialize_cpu();
+        assert_eq!(cpu.flags(), Flags::default());
+
+        cpu.set_flag(Flag::Z, true);
+        cpu.set_flag(Flag::C, true);
+        assert_eq!(
+            cpu.flags(),
+            Flags {
+                sign: false,
+                zero: true,
+                aux_carry: false,
+                parity: false,
+                carry: true,
+            }
+        );
+
+        // 0xf1 + 0x0f is 0x00 with carry, auxiliary carry, zero and even parity
+        cpu.change_register(InstructionRegister::A, 0xf1u8 as i8);
+        cpu.change_register(InstructionRegister::B, 0x0f);
+        cpu.execute_add(&InstructionRegister::B);
+        assert_eq!(
+            cpu.flags(),
+            Flags {
+                sign: false,
+                zero: true,
+                aux_carry: true,
+                parity: true,
+                carry: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_flags_as_mask() {
+        let mut cpu = initialize_cpu();
+        assert_eq!(cpu.flags_as_mask(), 0);
+
+        cpu.set_flag(Flag::S, true);
+        cpu.set_flag(Flag::C, true);
+        assert_eq!(cpu.flags_as_mask(), 0b1_0001);
+
+        cpu.set_flags_from_mask(0b1110_1110);
+        assert!(!cpu.get_flag(Flag::S));
+        assert!(cpu.get_flag(Flag::Z));
+        assert!(cpu.get_flag(Flag::A));
+        assert!(cpu.get_flag(Flag::P));
+        assert!(!cpu.get_flag(Flag::C));
+        assert_eq!(cpu.flags_as_mask(), 0b0_1110);
+    }
+
     #[test]
     fn test_execute_ori() {
         let mut cpu = initialize_cpu();
@@ -1988,8 +3611,12 @@ mod tests {
         cpu.set_flag(Flag::Z, true);
         cpu.change_register(InstructionRegister::A, 74);
         cpu.execute_cpi(-64);
-        assert_eq!(cpu.get_flag(Flag::C), false);
+        assert!(cpu.get_flag(Flag::C));
         assert_eq!(cpu.get_flag(Flag::Z), false);
+
+        cpu.execute_cpi(74);
+        assert!(!cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::Z));
     }
 
     #[test]
@@ -2011,6 +3638,23 @@ mod tests {
         assert_eq!(cpu.get_register(InstructionRegister::A), -2);
         assert_eq!(cpu.get_flag(Flag::C), true);
         assert_eq!(cpu.get_flag(Flag::Z), false);
+
+        // -128 can't be negated, this used to panic
+        cpu.change_register(InstructionRegister::A, 0);
+        cpu.set_flag(Flag::C, false);
+        cpu.execute_sbi(-128);
+        assert_eq!(cpu.get_register(InstructionRegister::A), -128);
+        assert!(cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::S));
+        assert!(!cpu.get_flag(Flag::Z));
+        assert!(!cpu.get_flag(Flag::P));
+
+        cpu.change_register(InstructionRegister::A, 5);
+        cpu.set_flag(Flag::C, true);
+        cpu.execute_sbi(2);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 2);
+        assert!(!cpu.get_flag(Flag::C));
+        assert!(!cpu.get_flag(Flag::P));
     }
     #[test]
     fn test_execute_lxi() {
@@ -2054,6 +3698,10 @@ mod tests {
 
         assert_eq!(cpu.get_memory(123), 7);
         assert_eq!(cpu.get_memory(124), 70);
+
+        cpu.execute_shld(-1);
+        assert_eq!(cpu.get_memory(0xFFFF), 7);
+        assert_eq!(cpu.get_memory(0), 70);
     }
 
     #[test]
@@ -2066,6 +3714,13 @@ mod tests {
 
         assert_eq!(cpu.get_register(InstructionRegister::H), 1);
         assert_eq!(cpu.get_register(InstructionRegister::L), 15);
+
+        cpu.set_memory(0xFFFF, 3);
+        cpu.set_memory(0, 4);
+        cpu.execute_lhld(-1);
+
+        assert_eq!(cpu.get_register(InstructionRegister::H), 4);
+        assert_eq!(cpu.get_register(InstructionRegister::L), 3);
     }
 
     #[test]
@@ -2079,6 +3734,62 @@ mod tests {
         assert_eq!(cpu.get_program_counter(), 16702);
     }
 
+    #[test]
+    fn test_execute_pchl_negative_registers() {
+        let mut cpu = initialize_cpu();
+
+        cpu.change_register(InstructionRegister::H, -1);
+        cpu.change_register(InstructionRegister::L, -1);
+        cpu.execute_pchl();
+        assert_eq!(cpu.get_program_counter(), 0xffff);
+
+        cpu.change_register(InstructionRegister::H, 0x12);
+        cpu.change_register(InstructionRegister::L, -128);
+        cpu.execute_pchl();
+        assert_eq!(cpu.get_program_counter(), 0x1280);
+    }
+
+    #[test]
+    fn test_execute_ret() {
+        let mut cpu = initialize_cpu();
+
+        cpu.set_stack_pointer(0x2000);
+        cpu.set_memory(0x2000, 0x34);
+        cpu.set_memory(0x2001, 0x12);
+        cpu.execute_ret();
+
+        assert_eq!(cpu.get_program_counter(), 0x1234);
+        assert_eq!(cpu.get_stack_pointer(), 0x2002);
+    }
+
+    #[test]
+    fn test_jumps_dont_increment_program_counter() {
+        let mut cpu = initialize_cpu();
+
+        // return address 0x0007 is on the stack, RET has to land exactly on it
+        let mut instructions = vec![None; 65536];
+        instructions[0] = Some(Instruction::Intermediate16Bit(
+            InstructionCommand::Lxi,
+            InstructionRegisterPair::HL,
+            0x0004,
+        ));
+        instructions[3] = Some(Instruction::NoRegister(InstructionCommand::Pchl));
+        instructions[4] = Some(Instruction::Label(InstructionCommand::Jc, 0x0100));
+        instructions[7] = Some(Instruction::NoRegister(InstructionCommand::Hlt));
+        instructions[8] = Some(Instruction::NoRegister(InstructionCommand::Ret));
+        cpu.load_program(instructions);
+        cpu.set_stack_pointer(0x2000);
+        cpu.set_memory(0x2000, 0x07);
+
+        cpu.set_program_counter(8);
+        cpu.step().unwrap();
+        assert_eq!(cpu.get_program_counter(), 0x0007);
+
+        // PCHL jumps to HL, the JC there isn't taken and continues behind it
+        assert_eq!(cpu.run_until_break(0), BreakReason::Halt);
+        assert_eq!(cpu.get_program_counter(), 0x0008);
+    }
+
     #[test]
     fn test_execute_jmp() {
         let mut cpu = initialize_cpu();
@@ -2217,4 +3928,569 @@ mod tests {
         assert_eq!(Flag::P.get_index(), 5);
         assert_eq!(Flag::C.get_index(), 7);
     }
+
+    #[derive(Debug, Default)]
+    struct MockBus {
+        input: u8,
+        written: Rc<RefCell<Vec<(u8, u8)>>>,
+    }
+
+    impl IoBus for MockBus {
+        fn read(&mut self, _port: u8) -> u8 {
+            self.input
+        }
+
+        fn write(&mut self, port: u8, value: u8) {
+            self.written.borrow_mut().push((port, value));
+        }
+    }
+
+    #[test]
+    fn test_execute_in() {
+        let mut cpu = initialize_cpu();
+        cpu.set_io_bus(Box::new(MockBus {
+            input: 0xAB,
+            ..Default::default()
+        }));
+
+        cpu.execute_in(3);
+        assert_eq!(cpu.get_register(InstructionRegister::A) as u8, 0xAB);
+    }
+
+    #[test]
+    fn test_execute_out() {
+        let mut cpu = initialize_cpu();
+        let written = Rc::new(RefCell::new(Vec::new()));
+        cpu.set_io_bus(Box::new(MockBus {
+            input: 0,
+            written: Rc::clone(&written),
+        }));
+
+        cpu.change_register(InstructionRegister::A, -1);
+        cpu.execute_out(0x10);
+        cpu.change_register(InstructionRegister::A, 42);
+        cpu.execute_out(2);
+
+        assert_eq!(*written.borrow(), vec![(0x10, 0xFF), (2, 42)]);
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut instructions = vec![None; 65536];
+        instructions[0] = Some(Instruction::NoRegister(InstructionCommand::Ei));
+        instructions[1] = Some(Instruction::NoRegister(InstructionCommand::Nop));
+        instructions[2] = Some(Instruction::NoRegister(InstructionCommand::Hlt));
+        instructions[0x08] = Some(Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let mut cpu = initialize_cpu();
+        cpu.set_stack_pointer(0x1000);
+        cpu.request_interrupt(1);
+        cpu.run_indexed(&instructions, false);
+
+        // HLT at 0x08 was executed and the return address was pushed
+        assert_eq!(cpu.get_program_counter(), 0x09);
+        assert_eq!(cpu.get_stack_pointer(), 0x0FFE);
+        assert_eq!(cpu.get_memory(0x0FFE), 1);
+        assert_eq!(cpu.get_memory(0x0FFF), 0);
+        assert!(!cpu.interrupts_enabled);
+    }
+
+    #[test]
+    fn test_interrupt_masked() {
+        let mut instructions = vec![None; 65536];
+        instructions[0] = Some(Instruction::NoRegister(InstructionCommand::Nop));
+        instructions[1] = Some(Instruction::NoRegister(InstructionCommand::Hlt));
+        instructions[0x08] = Some(Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let mut cpu = initialize_cpu();
+        cpu.execute_ei();
+        cpu.execute_di();
+        cpu.request_interrupt(1);
+        cpu.run_indexed(&instructions, false);
+
+        assert_eq!(cpu.get_program_counter(), 0x02);
+        assert_eq!(cpu.pending_interrupt, Some(1));
+    }
+
+    #[test]
+    fn test_state_equality() {
+        let mut assembler = assembler::Assembler::new(
+            "data/test/end_to_end.asm".to_owned(),
+            "test_state_equality_binary".to_owned(),
+        );
+        assembler.assemble().unwrap();
+        let instructions = assembler.disassemble_indexed().unwrap();
+        std::fs::remove_file("test_state_equality_binary").unwrap();
+
+        let mut first = initialize_cpu();
+        let mut second = initialize_cpu();
+        first.run_program(&instructions);
+        second.run_program(&instructions);
+        assert_eq!(first.state(), second.state());
+        assert_eq!(first.snapshot(), second.snapshot());
+
+        // memory is only part of the snapshot
+        second.set_memory(0x4000, 1);
+        assert_eq!(first.state(), second.state());
+        assert_ne!(first.snapshot(), second.snapshot());
+
+        second.change_register(InstructionRegister::E, 1);
+        assert_ne!(first.state(), second.state());
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut instructions = HashMap::new();
+        instructions.insert(
+            0,
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 42, InstructionRegister::A),
+        );
+        instructions.insert(
+            2,
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 0x100),
+        );
+        instructions.insert(5, Instruction::NoRegister(InstructionCommand::Stc));
+        instructions.insert(6, Instruction::NoRegister(InstructionCommand::Hlt));
+        let instructions = assembler::index_by_address(instructions);
+
+        let mut cpu = initialize_cpu();
+        cpu.execute(instructions[0].as_ref().unwrap());
+        cpu.incr_program_counter(instructions[0].as_ref().unwrap());
+
+        let state = cpu.snapshot();
+        assert_eq!(state.program_counter, 2);
+        assert_eq!(state.memory.len(), 0);
+
+        cpu.run_indexed(&instructions, false);
+        assert_eq!(cpu.get_memory(0x100), 42);
+        assert!(cpu.get_flag(Flag::C));
+        assert_ne!(cpu.snapshot(), state);
+
+        cpu.restore(&state);
+        assert_eq!(cpu.snapshot(), state);
+        assert_eq!(cpu.get_program_counter(), 2);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 42);
+        assert_eq!(cpu.get_memory(0x100), 0);
+        assert_eq!(cpu.memory.len(), 65536);
+        assert!(!cpu.get_flag(Flag::C));
+
+        // resuming from the snapshot gives the same result again
+        cpu.run_indexed(&instructions, false);
+        assert_eq!(cpu.get_memory(0x100), 42);
+        assert_eq!(cpu.get_program_counter(), 7);
+    }
+
+    #[test]
+    fn test_step_with_changes() {
+        let mut instructions = HashMap::new();
+        instructions.insert(
+            0,
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 5, InstructionRegister::A),
+        );
+        instructions.insert(
+            2,
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 0x42),
+        );
+        instructions.insert(
+            5,
+            Instruction::PairRegister(InstructionCommand::Push, InstructionRegisterPair::BC),
+        );
+        instructions.insert(6, Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let mut cpu = CpuBuilder::new()
+            .stack_pointer(0x100)
+            .register(InstructionRegister::B, 1)
+            .build();
+        cpu.load_program(assembler::index_by_address(instructions));
+
+        assert_eq!(cpu.step_with_changes().unwrap(), vec![]);
+        assert_eq!(cpu.step_with_changes().unwrap(), vec![(0x42, 5)]);
+        // C is 0, the same as the memory it's pushed to
+        assert_eq!(cpu.step_with_changes().unwrap(), vec![(0xff, 1)]);
+        assert_eq!(cpu.step_with_changes().unwrap(), vec![]);
+        assert!(cpu.is_halted());
+        assert_eq!(cpu.program_counter(), 7);
+
+        // nothing is recorded outside of step_with_changes
+        cpu.set_memory(0x43, 1);
+        assert!(cpu.memory_changes.is_none());
+    }
+
+    #[test]
+    fn test_step_without_instruction() {
+        // nothing is loaded yet
+        let mut cpu = initialize_cpu();
+        assert!(matches!(
+            cpu.step_with_changes(),
+            Err(AssemblerError::InvalidInstruction(0, 0))
+        ));
+        assert!(cpu.memory_changes.is_none());
+
+        // the slot behind the program is empty
+        cpu.load_program(assembler::assemble_str_indexed("MVI A,1\n").unwrap());
+        assert_eq!(cpu.run_until_break(0), BreakReason::NoInstruction(2));
+        assert_eq!(cpu.get_register(InstructionRegister::A), 1);
+
+        cpu.set_stack_pointer(0x100);
+        assert_eq!(cpu.call_subroutine(0), RunOutcome::NoInstruction(2));
+    }
+
+    #[test]
+    fn test_deadlock_detection() {
+        let source = "MVI A, 3\nloop: DCR A\nJNZ loop\nHERE: JMP HERE\n";
+
+        let mut cpu = initialize_cpu();
+        cpu.load_program(assembler::assemble_str_indexed(source).unwrap());
+        cpu.set_deadlock_detection(true);
+
+        // jumping back to an earlier address is fine
+        assert_eq!(cpu.run_until_break(0), BreakReason::Deadlock(6));
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0);
+        assert_eq!(cpu.get_program_counter(), 6);
+
+        // a conditional jump that is taken doesn't change the flags either
+        let mut cpu = initialize_cpu();
+        cpu.load_program(assembler::assemble_str_indexed("HERE: JNZ $\nHLT\n").unwrap());
+        cpu.set_deadlock_detection(true);
+        assert_eq!(cpu.run_until_break(0), BreakReason::Deadlock(0));
+
+        cpu.set_flag(Flag::Z, true);
+        assert_eq!(cpu.run_until_break(0), BreakReason::Halt);
+    }
+
+    #[test]
+    fn test_call_subroutine() {
+        // multiplies B by C into A
+        let source = "HLT\nmul: MVI A, 0\nloop: ADD B\nDCR C\nJNZ loop\nRET\n";
+
+        let mut cpu = CpuBuilder::new()
+            .stack_pointer(0x100)
+            .register(InstructionRegister::B, 6)
+            .register(InstructionRegister::C, 7)
+            .build();
+        cpu.load_program(assembler::assemble_str_indexed(source).unwrap());
+
+        assert_eq!(cpu.call_subroutine(1), RunOutcome::Returned);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 42);
+        assert_eq!(cpu.get_register(InstructionRegister::C), 0);
+        assert_eq!(cpu.get_stack_pointer(), 0x100);
+        assert!(!cpu.is_halted());
+
+        assert_eq!(cpu.call_subroutine(0), RunOutcome::Halted(0));
+        assert!(cpu.is_halted());
+    }
+
+    #[test]
+    fn test_run_cycles() {
+        let mut instructions = HashMap::new();
+        instructions.insert(
+            0,
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 1, InstructionRegister::A),
+        );
+        instructions.insert(
+            2,
+            Instruction::SingleRegister(InstructionCommand::Inr, InstructionRegister::A),
+        );
+        instructions.insert(3, Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let mut cpu = initialize_cpu();
+        cpu.load_program(assembler::index_by_address(instructions));
+
+        // MVI takes 7 t-states
+        assert_eq!(cpu.run_cycles(0, 6), 0);
+        assert_eq!(cpu.get_program_counter(), 0);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0);
+
+        assert_eq!(cpu.run_cycles(0, 11), 7);
+        assert_eq!(cpu.get_program_counter(), 2);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 1);
+
+        assert_eq!(cpu.run_cycles(2, 100), 12);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 2);
+        assert!(cpu.is_halted());
+    }
+
+    #[test]
+    fn test_run_cycles_interrupt() {
+        let mut instructions = HashMap::new();
+        instructions.insert(0, Instruction::NoRegister(InstructionCommand::Nop));
+        instructions.insert(1, Instruction::NoRegister(InstructionCommand::Hlt));
+        instructions.insert(
+            0x08,
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 1, InstructionRegister::A),
+        );
+        instructions.insert(0x0A, Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let mut cpu = initialize_cpu();
+        cpu.load_program(assembler::index_by_address(instructions));
+        cpu.set_stack_pointer(0x1000);
+
+        // the RST takes 11 t-states, the NOP at 0 would have fit
+        cpu.execute_ei();
+        cpu.request_interrupt(1);
+        assert_eq!(cpu.run_cycles(0, 10), 0);
+        assert_eq!(cpu.get_program_counter(), 0);
+        assert_eq!(cpu.pending_interrupt, Some(1));
+
+        // the RST fits but the MVI at the vector doesn't
+        assert_eq!(cpu.run_cycles(0, 17), 11);
+        assert_eq!(cpu.get_program_counter(), 0x08);
+        assert_eq!(cpu.get_stack_pointer(), 0x0FFE);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 0);
+        assert_eq!(cpu.pending_interrupt, None);
+    }
+
+    #[test]
+    fn test_run_until_break() {
+        let mut instructions = HashMap::new();
+        instructions.insert(
+            0,
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 1, InstructionRegister::A),
+        );
+        instructions.insert(
+            2,
+            Instruction::SingleRegister(InstructionCommand::Inr, InstructionRegister::A),
+        );
+        instructions.insert(
+            3,
+            Instruction::SingleRegister(InstructionCommand::Inr, InstructionRegister::A),
+        );
+        instructions.insert(4, Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let mut cpu = initialize_cpu();
+        cpu.load_program(assembler::index_by_address(instructions));
+        cpu.add_breakpoint(3);
+
+        assert_eq!(cpu.run_until_break(0), BreakReason::Breakpoint(3));
+        assert_eq!(cpu.get_program_counter(), 3);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 2);
+
+        // continuing from the breakpoint runs until HLT
+        assert_eq!(cpu.run_until_break(3), BreakReason::Halt);
+        assert_eq!(cpu.get_program_counter(), 5);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 3);
+
+        cpu.remove_breakpoint(3);
+        assert_eq!(cpu.run_until_break(0), BreakReason::Halt);
+        assert_eq!(cpu.get_register(InstructionRegister::A), 3);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut instructions = HashMap::new();
+        instructions.insert(
+            0,
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 42, InstructionRegister::A),
+        );
+        instructions.insert(
+            2,
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 0x10),
+        );
+        instructions.insert(
+            5,
+            Instruction::Intermediate16BitNoReg(InstructionCommand::Sta, 0x20),
+        );
+        instructions.insert(8, Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let mut cpu = initialize_cpu();
+        cpu.load_program(assembler::index_by_address(instructions));
+        cpu.add_watchpoint(0x20);
+
+        assert_eq!(cpu.run_until_break(0), BreakReason::Watchpoint(0x20, 42));
+        assert_eq!(cpu.get_program_counter(), 8);
+        assert_eq!(cpu.get_memory(0x10), 42);
+        assert_eq!(cpu.get_memory(0x20), 42);
+
+        cpu.remove_watchpoint(0x20);
+        assert_eq!(cpu.run_until_break(0), BreakReason::Halt);
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut instructions = HashMap::new();
+        instructions.insert(
+            0,
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 5, InstructionRegister::A),
+        );
+        instructions.insert(2, Instruction::NoRegister(InstructionCommand::Stc));
+        instructions.insert(3, Instruction::Label(InstructionCommand::Jmp, 0x10));
+        instructions.insert(0x10, Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let records = Rc::new(RefCell::new(Vec::new()));
+        let collected = Rc::clone(&records);
+
+        let mut cpu = initialize_cpu();
+        cpu.set_trace(Box::new(move |record: &TraceRecord| {
+            collected.borrow_mut().push(record.clone())
+        }));
+        cpu.run(instructions, false);
+
+        let records = records.borrow();
+        let program_counters: Vec<u16> = records
+            .iter()
+            .map(|record| record.program_counter)
+            .collect();
+        assert_eq!(program_counters, vec![0, 2, 3, 0x10]);
+
+        // records are taken before the instruction is executed
+        assert_eq!(records[0].accumulator, 0);
+        assert_eq!(records[1].accumulator, 5);
+        assert!(!records[1].flags.carry);
+        assert!(records[2].flags.carry);
+        assert_eq!(
+            records[3].instruction,
+            Instruction::NoRegister(InstructionCommand::Hlt)
+        );
+    }
+
+    #[test]
+    fn test_print_status() {
+        let mut cpu = initialize_cpu();
+        cpu.change_register(InstructionRegister::A, 42);
+        cpu.set_flag(Flag::C, true);
+        cpu.set_stack_pointer(1234);
+        cpu.set_memory(0x100, -7);
+
+        let mut buffer = Vec::new();
+        cpu.print_status(&mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+
+        assert!(output.contains("A: 0b00101010 (42 / 42)"));
+        assert!(output.contains("C: true"));
+        assert!(output.contains("Stack Pointer: 1234"));
+        assert!(output.contains("256: -7"));
+    }
+
+    #[test]
+    fn test_print_status_radix() {
+        let mut cpu = initialize_cpu();
+        cpu.change_register(InstructionRegister::B, -1);
+        cpu.set_stack_pointer(0x1234);
+        cpu.set_memory(0x1000, 0x42);
+
+        let mut buffer = Vec::new();
+        cpu.print_status_radix(&mut buffer, Radix::Hex).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+
+        assert!(output.contains("B: 0xff"));
+        assert!(output.contains("Stack Pointer: 0x1234"));
+        assert!(output.contains("Program counter: 0x0000"));
+        assert!(output.contains("0x1000: 0x42"));
+
+        let mut buffer = Vec::new();
+        cpu.print_status_radix(&mut buffer, Radix::Binary).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+
+        assert!(output.contains("B: 0b11111111"));
+        assert!(output.contains("0b0001000000000000: 0b01000010"));
+    }
+
+    #[test]
+    fn test_register_display() {
+        let mut cpu = initialize_cpu();
+        cpu.change_register(InstructionRegister::B, -1);
+
+        assert_eq!(
+            cpu.register_display(InstructionRegister::B),
+            "B: 0b11111111 (-1 / 255)"
+        );
+        assert_eq!(
+            cpu.register_display(InstructionRegister::C),
+            "C: 0b00000000 (0 / 0)"
+        );
+    }
+
+    #[test]
+    fn test_print_run() {
+        let mut instructions = HashMap::new();
+        instructions.insert(
+            0,
+            Instruction::IntermediateRegister(InstructionCommand::Mvi, 3, InstructionRegister::B),
+        );
+        instructions.insert(2, Instruction::NoRegister(InstructionCommand::Hlt));
+
+        let mut cpu = initialize_cpu();
+        let mut buffer = Vec::new();
+        cpu.print_run(&assembler::index_by_address(instructions), &mut buffer)
+            .unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+
+        assert!(output.starts_with("Initial status:"));
+        assert!(output.contains("MVI B, 0x03"));
+        assert!(output.contains("B: 0b00000011 (3 / 3)"));
+        assert!(output.contains("Execution finished"));
+    }
+
+    #[test]
+    fn test_cpu_builder() {
+        let mut cpu = CpuBuilder::new()
+            .memory_size(1024)
+            .program_counter(0x10)
+            .stack_pointer(0x3FF)
+            .register(InstructionRegister::B, 42)
+            .register(InstructionRegister::A, -1)
+            .build();
+
+        assert_eq!(cpu.get_program_counter(), 0x10);
+        assert_eq!(cpu.get_stack_pointer(), 0x3FF);
+        assert_eq!(cpu.get_register(InstructionRegister::B), 42);
+        assert_eq!(cpu.get_register(InstructionRegister::A), -1);
+        assert_eq!(cpu.get_register(InstructionRegister::C), 0);
+        assert_eq!(cpu.memory.len(), 1024);
+
+        // addresses beyond the configured size wrap around
+        cpu.set_memory(1024 + 5, 7);
+        assert_eq!(cpu.get_memory(5), 7);
+    }
+
+    #[test]
+    fn test_fill_memory() {
+        let seeded = |seed| {
+            CpuBuilder::new()
+                .memory_size(1001)
+                .fill_memory(FillPattern::Seed(seed))
+                .build()
+        };
+
+        assert_eq!(seeded(42).memory, seeded(42).memory);
+        assert_ne!(seeded(42).memory, seeded(43).memory);
+        assert_eq!(seeded(0).memory.len(), 1001);
+        assert!(seeded(0).memory.iter().any(|value| *value != 0));
+
+        let cpu = CpuBuilder::new().fill_memory(FillPattern::Zero).build();
+        assert!(cpu.memory.iter().all(|value| *value == 0));
+    }
+
+    #[cfg(feature = "i8085")]
+    #[test]
+    fn test_execute_sim() {
+        let mut cpu = initialize_cpu();
+
+        // SOD without SOE is ignored
+        cpu.change_register(InstructionRegister::A, 0b1000_0000u8 as i8);
+        cpu.execute_sim();
+        assert!(!cpu.get_serial_output());
+
+        cpu.change_register(InstructionRegister::A, 0b1100_1101u8 as i8);
+        cpu.execute_sim();
+        assert!(cpu.get_serial_output());
+        assert_eq!(cpu.i8085.interrupt_masks, 0b101);
+
+        cpu.change_register(InstructionRegister::A, 0b0100_0000);
+        cpu.execute_sim();
+        assert!(!cpu.get_serial_output());
+        assert_eq!(cpu.i8085.interrupt_masks, 0b101);
+    }
+
+    #[cfg(feature = "i8085")]
+    #[test]
+    fn test_execute_rim() {
+        let mut cpu = initialize_cpu();
+        cpu.i8085.interrupt_masks = 0b011;
+        cpu.set_serial_input(true);
+        cpu.execute_ei();
+
+        cpu.execute_rim();
+        assert_eq!(cpu.get_register(InstructionRegister::A) as u8, 0b1000_1011);
+    }
 }
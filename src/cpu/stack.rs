@@ -0,0 +1,14 @@
+use std::fmt;
+
+// SP has to stay within floor..=ceiling, otherwise hook is called with the new SP
+pub struct StackGuard {
+    pub floor: u16,
+    pub ceiling: u16,
+    pub hook: Box<dyn FnMut(u16)>,
+}
+
+impl fmt::Debug for StackGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StackGuard({:#06x}..={:#06x})", self.floor, self.ceiling)
+    }
+}
@@ -0,0 +1,69 @@
+use crate::cpu::{Cpu, Flags};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CpuState {
+    pub registers: Vec<i8>,
+    pub flags: Flags,
+    pub stack_pointer: u16,
+    pub program_counter: u16,
+    // memory up to the last byte that isn't 0, the rest is 0
+    pub memory: Vec<i8>,
+    pub interrupts_enabled: bool,
+    pub pending_interrupt: Option<u8>,
+}
+
+impl Cpu {
+    pub fn snapshot(&self) -> CpuState {
+        let used = self
+            .memory
+            .iter()
+            .rposition(|value| *value != 0)
+            .map_or(0, |address| address + 1);
+
+        CpuState {
+            registers: self.registers.clone(),
+            flags: self.flags(),
+            stack_pointer: self.stack_pointer,
+            program_counter: self.program_counter,
+            memory: self.memory[..used].to_vec(),
+            interrupts_enabled: self.interrupts_enabled,
+            pending_interrupt: self.pending_interrupt,
+        }
+    }
+
+    // same as snapshot without memory, handy to compare two cpus, restoring it clears memory
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            memory: Vec::new(),
+            ..self.snapshot()
+        }
+    }
+
+    pub fn restore(&mut self, state: &CpuState) {
+        self.registers = state.registers.clone();
+        self.set_flags(state.flags);
+        self.stack_pointer = state.stack_pointer;
+        self.program_counter = state.program_counter;
+
+        let size = self.memory.len();
+        let mut memory = state.memory.clone();
+        memory.resize(size, 0);
+        let old_memory = std::mem::replace(&mut self.memory, memory);
+
+        // like set_memory, loaded code under changed bytes is decoded again
+        let changed: Vec<u16> = (0..size)
+            .filter(|address| old_memory[*address] != self.memory[*address])
+            .map(|address| address as u16)
+            .collect();
+        for address in changed {
+            self.redecode(address);
+        }
+
+        self.interrupts_enabled = state.interrupts_enabled;
+        self.pending_interrupt = state.pending_interrupt;
+    }
+}
@@ -0,0 +1,12 @@
+use std::fmt;
+
+// called with register C, the DE pair and the memory instead of the code at its address
+pub type SyscallFn = Box<dyn FnMut(u8, u16, &[i8])>;
+
+pub struct SyscallHandler(pub SyscallFn);
+
+impl fmt::Debug for SyscallHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SyscallHandler")
+    }
+}
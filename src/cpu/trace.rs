@@ -0,0 +1,20 @@
+use crate::assembler::Instruction;
+use crate::cpu::Flags;
+use std::fmt;
+
+// state right before the instruction at program_counter is executed
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    pub program_counter: u16,
+    pub instruction: Instruction,
+    pub accumulator: i8,
+    pub flags: Flags,
+}
+
+pub struct Tracer(pub Box<dyn FnMut(&TraceRecord)>);
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Tracer")
+    }
+}
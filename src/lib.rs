@@ -0,0 +1,7 @@
+extern crate pest;
+#[macro_use]
+extern crate pest_derive;
+
+pub mod assembler;
+pub mod cli;
+pub mod cpu;
@@ -1,17 +1,20 @@
-extern crate pest;
-#[macro_use]
-extern crate pest_derive;
-
-mod assembler;
-mod cpu;
+use emu8080::cli;
+use std::io;
+use std::process;
 
 fn main() {
-    let mut cpu = cpu::initialize_cpu();
-
-    let assembler = assembler::Assembler::new("data/test/end_to_end.asm".to_owned(), "output".to_owned());
-
-    assembler.assemble();
-    let instructions = assembler.disassemble("output".to_owned());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match cli::parse_args(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n{}", error, cli::USAGE);
+            process::exit(2);
+        }
+    };
 
-    cpu.run(instructions, true);
+    let stdin = io::stdin();
+    if let Err(error) = cli::run(&options, &mut stdin.lock(), &mut io::stdout()) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
 }